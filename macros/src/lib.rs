@@ -0,0 +1,153 @@
+//! The [`imex!`](./macro.imex.html) macro, which parses an IMEx at compile time. It's re-exported
+//! by the `imex` crate when its `macros` feature is enabled, and should be used from there.
+
+extern crate proc_macro;
+
+use imex_syntax::{IMEx, IMExVal, QuantifiedIMExVal, Quantifier};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::convert::TryFrom;
+use syn::{parse_macro_input, LitStr};
+
+/// Parses an IMEx string literal at compile time, and expands to the
+/// [`Pattern`](https://docs.rs/imex/*/imex/struct.Pattern.html) it describes. The Pattern is
+/// built from the parsed IMEx, so it isn't parsed again when the program runs.
+///
+/// # Error
+/// Fails to compile if the IMEx is invalid, pointing at the literal with the same diagnostic
+/// that [`ParseError::render`](https://docs.rs/imex/*/imex/expression/struct.ParseError.html#method.render)
+/// gives.
+#[proc_macro]
+pub fn imex(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let imex_str = literal.value();
+    match IMEx::try_from(imex_str.as_str()) {
+        Ok(imex) => {
+            let imex = imex_tokens(&imex);
+            quote!(::imex::Pattern::from(#imex)).into()
+        }
+        Err(error) => syn::Error::new(error_span(&literal, error.span()), error.render(&imex_str))
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// The span of `span`, the bytes of the IMEx where it failed to parse, within `literal`. The
+/// whole literal is used if the compiler can't point into it, or if the IMEx has escapes, since
+/// then its bytes don't match those of the literal.
+fn error_span(literal: &LitStr, span: std::ops::Range<usize>) -> Span {
+    let token = literal.token().to_string();
+    let value = literal.value();
+    token
+        .find('"')
+        .map(|quote| quote + 1)
+        .filter(|&start| {
+            token[start..].starts_with(&value) && token[start + value.len()..].starts_with('"')
+        })
+        .and_then(|start| {
+            let end = (start + span.end).max(start + span.start + 1);
+            literal.token().subspan(start + span.start..end)
+        })
+        .unwrap_or_else(|| literal.span())
+}
+
+fn imex_tokens(imex: &IMEx) -> TokenStream {
+    let vals = imex.vals.iter().map(quantified_tokens);
+    quote!(::imex::expression::IMEx::new(::std::vec![#(#vals),*]))
+}
+
+fn quantified_tokens(val: &QuantifiedIMExVal) -> TokenStream {
+    let quantifier = quantifier_tokens(val.get_quantifier());
+    let val = val_tokens(val.get_val());
+    quote!(::imex::expression::QuantifiedIMExVal::new(#val, #quantifier))
+}
+
+fn quantifier_tokens(quantifier: &Quantifier) -> TokenStream {
+    match quantifier {
+        Quantifier::Infinite => quote!(::imex::expression::Quantifier::Infinite),
+        Quantifier::AtLeastOnce => quote!(::imex::expression::Quantifier::AtLeastOnce),
+        Quantifier::Optional => quote!(::imex::expression::Quantifier::Optional),
+        Quantifier::Finite(count) => quote!(::imex::expression::Quantifier::Finite(#count)),
+        Quantifier::Range(min, Some(max)) => quote!(
+            ::imex::expression::Quantifier::Range(#min, ::std::option::Option::Some(#max))
+        ),
+        Quantifier::Range(min, None) => quote!(
+            ::imex::expression::Quantifier::Range(#min, ::std::option::Option::None)
+        ),
+        Quantifier::Param(name) => quote!(
+            ::imex::expression::Quantifier::Param(::std::string::String::from(#name))
+        ),
+    }
+}
+
+fn val_tokens(val: &IMExVal) -> TokenStream {
+    match val {
+        IMExVal::Single(index) => quote!(::imex::expression::IMExVal::Single(#index)),
+        IMExVal::Named(name) => quote!(
+            ::imex::expression::IMExVal::Named(::std::string::String::from(#name))
+        ),
+        IMExVal::Group(imex) => {
+            let imex = imex_tokens(imex);
+            quote!(::imex::expression::IMExVal::Group(#imex))
+        }
+        IMExVal::NamedGroup(name, imex) => {
+            let imex = imex_tokens(imex);
+            quote!(::imex::expression::IMExVal::NamedGroup(
+                ::std::string::String::from(#name),
+                #imex
+            ))
+        }
+        IMExVal::Call(name, imex) => {
+            let imex = imex_tokens(imex);
+            quote!(::imex::expression::IMExVal::Call(
+                ::std::string::String::from(#name),
+                #imex
+            ))
+        }
+        IMExVal::Alternation(imexes) => {
+            let imexes = imexes.iter().map(imex_tokens);
+            quote!(::imex::expression::IMExVal::Alternation(
+                ::std::vec![#(#imexes),*]
+            ))
+        }
+        IMExVal::Weighted(choice) => {
+            let alternatives = choice.get_alternatives().iter().map(|(imex, weight)| {
+                let imex = imex_tokens(imex);
+                quote!((#imex, #weight))
+            });
+            quote!(::imex::expression::IMExVal::Weighted(
+                ::imex::expression::WeightedChoice::new(::std::vec![#(#alternatives),*])
+            ))
+        }
+        IMExVal::Class(class) => match class.get_indices() {
+            Some(indices) => quote!(::imex::expression::IMExVal::Class(
+                ::imex::expression::SourceClass::new(::std::vec![#(#indices),*])
+            )),
+            None => quote!(::imex::expression::IMExVal::Class(
+                ::imex::expression::SourceClass::any()
+            )),
+        },
+        IMExVal::Sorted(sorted) => {
+            let sources = sorted.get_sources().iter().map(val_tokens);
+            quote!(::imex::expression::IMExVal::Sorted(
+                ::imex::expression::SortedMerge::new(::std::vec![#(#sources),*])
+            ))
+        }
+        IMExVal::Discard(val) => {
+            let val = val_tokens(val);
+            quote!(::imex::expression::IMExVal::Discard(::std::boxed::Box::new(#val)))
+        }
+        IMExVal::Conditional(conditional) => {
+            let source = val_tokens(conditional.get_source());
+            let negated = conditional.is_negated();
+            let imex = imex_tokens(conditional.get_imex());
+            quote!(::imex::expression::IMExVal::Conditional(
+                ::imex::expression::Conditional::new(#source, #negated, #imex)
+            ))
+        }
+        IMExVal::Anchor(val) => {
+            let val = val_tokens(val);
+            quote!(::imex::expression::IMExVal::Anchor(::std::boxed::Box::new(#val)))
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{IMExIter, IntoPattern};
+use std::io::Result;
+
+/// An iterator of any type that gives items of type `I`. Iterators of different types can be
+/// merged by boxing them into BoxedIters, as
+/// [`IntoBoxedIters`](./trait.IntoBoxedIters.html) does.
+pub type BoxedIter<'a, I> = Box<dyn Iterator<Item = I> + 'a>;
+
+/// Conversion of a tuple of iterators into [`BoxedIter`](./type.BoxedIter.html)s, so iterators of
+/// different types can be merged, as long as they give the same type of item. Implemented for
+/// tuples of up to 8 values that can be turned into iterators, like `("abc".chars(), vec!['x'])`.
+pub trait IntoBoxedIters<'a, I> {
+    /// Boxes each iterator of self, in order.
+    fn into_boxed_iters(self) -> Vec<BoxedIter<'a, I>>;
+}
+
+macro_rules! impl_into_boxed_iters {
+    ($($iter:ident),+) => {
+        impl<'a, I, $($iter),+> IntoBoxedIters<'a, I> for ($($iter,)+)
+        where
+            $($iter: IntoIterator<Item = I>, $iter::IntoIter: 'a,)+
+        {
+            #[allow(non_snake_case)]
+            fn into_boxed_iters(self) -> Vec<BoxedIter<'a, I>> {
+                let ($($iter,)+) = self;
+                vec![$(Box::new($iter.into_iter()) as BoxedIter<'a, I>),+]
+            }
+        }
+    };
+}
+
+impl_into_boxed_iters!(A);
+impl_into_boxed_iters!(A, B);
+impl_into_boxed_iters!(A, B, C);
+impl_into_boxed_iters!(A, B, C, D);
+impl_into_boxed_iters!(A, B, C, D, E);
+impl_into_boxed_iters!(A, B, C, D, E, F);
+impl_into_boxed_iters!(A, B, C, D, E, F, G);
+impl_into_boxed_iters!(A, B, C, D, E, F, G, H);
+
+impl<'a, I> IMExIter<BoxedIter<'a, I>, I> {
+    /// Constructs an [`IMExIter`](./struct.IMExIter.html) from a tuple of iterators of different
+    /// types, which are boxed so they can be merged, and an IMEx string or
+    /// [`Pattern`](./struct.Pattern.html). Otherwise, it's the same as
+    /// [`IMExIter::new`](./struct.IMExIter.html#method.new).
+    ///
+    /// # Error
+    /// Results in an error if the provided IMEx is invalid, if it has parameters, or if it refers
+    /// to iterators by name.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExIter;
+    ///
+    /// let merged = IMExIter::boxed(("1234".chars(), vec!['a', 'b', 'c']), "(001)*")
+    ///     .expect("Invalid IMEx")
+    ///     .collect::<String>();
+    ///
+    /// assert_eq!(merged, "12a34bc");
+    /// ```
+    pub fn boxed<U, X>(iters: U, imex: X) -> Result<Self>
+    where
+        U: IntoBoxedIters<'a, I>,
+        X: IntoPattern,
+    {
+        IMExIter::new(iters.into_boxed_iters(), imex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IMExMerges, Pattern};
+
+    #[test]
+    fn boxed_iters_keep_their_order() {
+        let iters = (
+            "ab".chars(),
+            vec!['c'],
+            Some('d'),
+            "ef".chars().rev(),
+            std::iter::once('g').chain("h".chars()),
+        )
+            .into_boxed_iters();
+        let items = iters.into_iter().flatten().collect::<String>();
+        assert_eq!(items, "abcdfegh");
+    }
+
+    #[test]
+    fn boxed_iters_merge_like_same_typed_iters() -> Result<()> {
+        let pattern = Pattern::new("(0[1-2]{2})*")?;
+        let boxed = IMExIter::boxed(
+            ("123".chars(), vec!['a', 'b'], "zyx".chars().rev()),
+            &pattern,
+        )?
+        .collect::<String>();
+        let same_typed = IMExIter::new(vec!["123".chars(), "ab".chars(), "xyz".chars()], &pattern)?
+            .collect::<String>();
+        assert_eq!(boxed, same_typed);
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_merges_borrow_iterators() -> Result<()> {
+        let lines = ["b".to_string(), "d".to_string()];
+        let merged = vec!["a", "c"]
+            .into_iter()
+            .imex_merge_mixed((lines.iter().map(String::as_str),), "(01)*")?
+            .collect::<Vec<&str>>();
+        assert_eq!(merged, vec!["a", "b", "c", "d"]);
+        Ok(())
+    }
+}
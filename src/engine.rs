@@ -0,0 +1,859 @@
+use crate::{
+    expression::{IMEx, IMExVal, QuantifiedIMExVal},
+    IMExIterator, Sources, Step,
+};
+use std::ops::Range;
+
+#[cfg(test)]
+mod tree;
+
+/// An [`IMEx`](./expression/struct.IMEx.html) compiled into a flat list of instructions, which is
+/// what an [`IMExIter`](./struct.IMExIter.html) runs.
+///
+/// Each instruction is followed by the instructions of its children. Running a Program keeps a
+/// stack of frames in a [`Cursor`](./struct.Cursor.html), one for each instruction that is in
+/// progress, holding its loop counters. The stack is allocated once, with room for the deepest
+/// nesting of the IMEx, so stepping a Program doesn't allocate.
+#[derive(PartialEq, Debug)]
+pub(crate) struct Program {
+    instrs: Vec<Instr>,
+    /// The indices listed by classes and sorted merges.
+    indices: Vec<isize>,
+    /// The position and weight of each alternative of the weighted choices.
+    alternatives: Vec<(usize, usize)>,
+    /// The number of classes, which each have a rotation.
+    classes: usize,
+    /// The largest number of frames that can be on the stack at once.
+    depth: usize,
+}
+
+/// An instruction of a [`Program`](./struct.Program.html). Instructions with children are
+/// followed by them, and `end` is the position just past the last of them.
+#[derive(PartialEq, Debug)]
+enum Instr {
+    /// An IMEx, whose children are Repeats that are run in order.
+    Seq { end: usize },
+    /// A quantified value, whose child is repeated.
+    Repeat {
+        end: usize,
+        minimum: usize,
+        maximum: Option<usize>,
+        zero_width: bool,
+    },
+    /// Takes an item from an iterator.
+    Take(isize),
+    /// A name that wasn't resolved, which never takes anything.
+    Nothing,
+    /// Takes an item from a class of iterators, listed in Program::indices, or from any
+    /// iterator if None. Its rotation is kept in Cursor::rotations.
+    Class {
+        indices: Option<Range<usize>>,
+        rotation: usize,
+    },
+    /// Takes the smallest item of the iterators listed in Program::indices.
+    Sorted(Range<usize>),
+    /// Runs the first of its children, which are Seqs, that takes anything.
+    Alternation { end: usize },
+    /// Runs one of its children at random, which are listed in Program::alternatives.
+    Weighted {
+        end: usize,
+        alternatives: Range<usize>,
+    },
+    /// Runs its child, discarding its items.
+    Discard { end: usize },
+    /// Runs its child, a Seq, only if the iterator at `source` isn't exhausted, or only if it is
+    /// when negated.
+    Conditional {
+        end: usize,
+        source: Option<isize>,
+        negated: bool,
+    },
+    /// Fails if the iterator at `source` isn't exhausted.
+    Anchor(Option<isize>),
+}
+
+/// The progress of a run of a [`Program`](./struct.Program.html).
+#[derive(Debug)]
+pub(crate) struct Cursor {
+    frames: Vec<Frame>,
+    /// The alternatives that can still be picked by the weighted choices on the stack. Each
+    /// Weighted frame owns the candidates from its start to the start of the next one.
+    candidates: Vec<usize>,
+    /// The rotation of each class, which carries over between repetitions.
+    rotations: Vec<usize>,
+    /// Whether a minimum wasn't met, once every frame has finished.
+    unmet: bool,
+    /// Whether the run ends as soon as a minimum isn't met, as when merging strictly, rather
+    /// than taking any more items.
+    strict: bool,
+}
+
+impl Cursor {
+    /// Makes the run end as soon as a minimum isn't met.
+    pub(crate) fn set_strict(&mut self) {
+        self.strict = true;
+    }
+}
+
+/// An instruction that is in progress.
+#[derive(Debug)]
+struct Frame {
+    position: usize,
+    state: FrameState,
+}
+
+#[derive(Debug)]
+enum FrameState {
+    Seq {
+        child: usize,
+        unmet: bool,
+    },
+    Repeat {
+        count: usize,
+        started: usize,
+        completed: usize,
+        unmet: bool,
+    },
+    Taken(bool),
+    Alternation {
+        child: usize,
+        committed: bool,
+    },
+    Weighted {
+        candidates: usize,
+        committed: bool,
+    },
+    Discard,
+    Conditional,
+}
+
+/// What the frame on top of the stack is told.
+enum Event {
+    /// It has no child in progress, and should make progress itself.
+    Continue,
+    /// Its child finished, either normally or by failing, and whether the child met its
+    /// minimums.
+    Finished { failed: bool, unmet: bool },
+}
+
+/// What the frame on top of the stack does in response to an Event.
+enum Action {
+    Continue,
+    Push(usize),
+    Choose(usize),
+    Take,
+    Pop { failed: bool, unmet: bool },
+}
+
+impl Program {
+    /// Compiles `imex` into a Program.
+    pub(crate) fn compile(imex: &IMEx) -> Program {
+        let mut program = Program {
+            instrs: vec![],
+            indices: vec![],
+            alternatives: vec![],
+            classes: 0,
+            depth: 0,
+        };
+        program.depth = program.compile_imex(imex);
+        program
+    }
+
+    /// Adds the instructions for `imex`, returning the number of frames they can need at once.
+    fn compile_imex(&mut self, imex: &IMEx) -> usize {
+        let position = self.open(Instr::Seq { end: 0 });
+        let depth = imex
+            .vals
+            .iter()
+            .map(|val| self.compile_quantified(val))
+            .max()
+            .unwrap_or(0);
+        self.close(position);
+        depth + 1
+    }
+
+    fn compile_quantified(&mut self, val: &QuantifiedIMExVal) -> usize {
+        let quantifier = val.get_quantifier();
+        let position = self.open(Instr::Repeat {
+            end: 0,
+            minimum: quantifier.minimum(),
+            maximum: quantifier.maximum(),
+            zero_width: val.get_val().is_zero_width(),
+        });
+        let depth = self.compile_val(val.get_val());
+        self.close(position);
+        depth + 1
+    }
+
+    fn compile_val(&mut self, val: &IMExVal) -> usize {
+        match val {
+            IMExVal::Single(index) => self.leaf(Instr::Take(*index)),
+            IMExVal::Named(_) => self.leaf(Instr::Nothing),
+            IMExVal::Group(imex) | IMExVal::NamedGroup(_, imex) | IMExVal::Call(_, imex) => {
+                self.compile_imex(imex)
+            }
+            IMExVal::Alternation(imexes) => {
+                let position = self.open(Instr::Alternation { end: 0 });
+                let depth = imexes
+                    .iter()
+                    .map(|imex| self.compile_imex(imex))
+                    .max()
+                    .unwrap_or(0);
+                self.close(position);
+                depth + 1
+            }
+            IMExVal::Weighted(choice) => {
+                // The alternatives are added before their instructions, so they stay together
+                // when they contain other weighted choices.
+                let start = self.alternatives.len();
+                self.alternatives.extend(
+                    choice
+                        .get_alternatives()
+                        .iter()
+                        .map(|(_, weight)| (0, *weight)),
+                );
+                let alternatives = start..self.alternatives.len();
+                let position = self.open(Instr::Weighted {
+                    end: 0,
+                    alternatives: alternatives.clone(),
+                });
+                let mut depth = 0;
+                for (alternative, (imex, _)) in alternatives.zip(choice.get_alternatives()) {
+                    self.alternatives[alternative].0 = self.instrs.len();
+                    depth = depth.max(self.compile_imex(imex));
+                }
+                self.close(position);
+                depth + 1
+            }
+            IMExVal::Class(class) => {
+                let indices = class.get_indices().map(|indices| self.add_indices(indices));
+                self.classes += 1;
+                self.leaf(Instr::Class {
+                    indices,
+                    rotation: self.classes - 1,
+                })
+            }
+            IMExVal::Sorted(sorted) => {
+                let indices = sorted
+                    .get_sources()
+                    .iter()
+                    .filter_map(IMExVal::source_index)
+                    .collect::<Vec<isize>>();
+                let indices = self.add_indices(&indices);
+                self.leaf(Instr::Sorted(indices))
+            }
+            IMExVal::Discard(val) => {
+                let position = self.open(Instr::Discard { end: 0 });
+                let depth = self.compile_val(val);
+                self.close(position);
+                depth + 1
+            }
+            IMExVal::Conditional(conditional) => {
+                let position = self.open(Instr::Conditional {
+                    end: 0,
+                    source: conditional.get_source().source_index(),
+                    negated: conditional.is_negated(),
+                });
+                let depth = self.compile_imex(conditional.get_imex());
+                self.close(position);
+                depth + 1
+            }
+            IMExVal::Anchor(val) => self.leaf(Instr::Anchor(val.source_index())),
+        }
+    }
+
+    fn leaf(&mut self, instr: Instr) -> usize {
+        self.instrs.push(instr);
+        1
+    }
+
+    fn add_indices(&mut self, indices: &[isize]) -> Range<usize> {
+        let start = self.indices.len();
+        self.indices.extend_from_slice(indices);
+        start..self.indices.len()
+    }
+
+    /// Adds an instruction whose children follow it, returning its position.
+    fn open(&mut self, instr: Instr) -> usize {
+        self.instrs.push(instr);
+        self.instrs.len() - 1
+    }
+
+    /// Sets the end of the instruction at `position` to just past the instructions added since.
+    fn close(&mut self, position: usize) {
+        let len = self.instrs.len();
+        match &mut self.instrs[position] {
+            Instr::Seq { end }
+            | Instr::Repeat { end, .. }
+            | Instr::Alternation { end }
+            | Instr::Weighted { end, .. }
+            | Instr::Discard { end }
+            | Instr::Conditional { end, .. } => *end = len,
+            _ => {}
+        }
+    }
+
+    /// Returns the position just past the instruction at `position` and its children.
+    fn end(&self, position: usize) -> usize {
+        match &self.instrs[position] {
+            Instr::Seq { end }
+            | Instr::Repeat { end, .. }
+            | Instr::Alternation { end }
+            | Instr::Weighted { end, .. }
+            | Instr::Discard { end }
+            | Instr::Conditional { end, .. } => *end,
+            _ => position + 1,
+        }
+    }
+
+    /// Puts a frame for the instruction at `position` on top of the stack.
+    fn push(&self, cursor: &mut Cursor, position: usize) {
+        let state = match &self.instrs[position] {
+            Instr::Seq { .. } => FrameState::Seq {
+                child: position + 1,
+                unmet: false,
+            },
+            Instr::Repeat { .. } => FrameState::Repeat {
+                count: 0,
+                started: 0,
+                completed: 0,
+                unmet: false,
+            },
+            Instr::Alternation { .. } => FrameState::Alternation {
+                child: position + 1,
+                committed: false,
+            },
+            Instr::Weighted { alternatives, .. } => {
+                let candidates = cursor.candidates.len();
+                cursor.candidates.extend(alternatives.clone());
+                FrameState::Weighted {
+                    candidates,
+                    committed: false,
+                }
+            }
+            Instr::Discard { .. } => FrameState::Discard,
+            Instr::Conditional { .. } => FrameState::Conditional,
+            _ => FrameState::Taken(false),
+        };
+        cursor.frames.push(Frame { position, state });
+    }
+
+    /// Removes the frame on top of the stack, along with its candidates.
+    fn pop(&self, cursor: &mut Cursor) {
+        if let Some(Frame {
+            state: FrameState::Weighted { candidates, .. },
+            ..
+        }) = cursor.frames.pop()
+        {
+            cursor.candidates.truncate(candidates);
+        }
+    }
+
+    /// Picks one of the candidates from `start` at random, by weight, and removes it from the
+    /// candidates. Returns the position of the picked alternative, or None if there are no
+    /// candidates left.
+    fn choose<T>(&self, cursor: &mut Cursor, start: usize, iters: &mut Sources<T>) -> Option<usize>
+    where
+        T: Iterator,
+    {
+        let candidates = &mut cursor.candidates;
+        let total = candidates[start..].iter().fold(0u64, |total, &c| {
+            total.saturating_add(self.alternatives[c].1 as u64)
+        });
+        let mut roll = iters.random_below(total);
+        for position in start..candidates.len() {
+            let (alternative, weight) = self.alternatives[candidates[position]];
+            if roll < weight as u64 {
+                candidates.remove(position);
+                return Some(alternative);
+            }
+            roll -= weight as u64;
+        }
+        None
+    }
+
+    /// Decides what the frame on top of the stack does in response to `event`.
+    fn act<T, I>(&self, frame: &mut Frame, event: Event, iters: &mut Sources<T>) -> Action
+    where
+        T: Iterator<Item = I>,
+    {
+        let position = frame.position;
+        match (&self.instrs[position], &mut frame.state, event) {
+            (Instr::Seq { end }, FrameState::Seq { child, unmet }, event) => match event {
+                Event::Continue if *child < *end => Action::Push(*child),
+                Event::Finished {
+                    failed: false,
+                    unmet: child_unmet,
+                } => {
+                    *unmet |= child_unmet;
+                    *child = self.end(*child);
+                    Action::Continue
+                }
+                _ => Action::Pop {
+                    failed: false,
+                    unmet: *unmet,
+                },
+            },
+            (
+                Instr::Repeat {
+                    minimum,
+                    maximum,
+                    zero_width,
+                    ..
+                },
+                FrameState::Repeat {
+                    count,
+                    started,
+                    completed,
+                    unmet,
+                },
+                event,
+            ) => {
+                let failed = match event {
+                    Event::Continue => {
+                        if maximum.map_or(true, |max| *started < max) {
+                            *started += 1;
+                            *count = 0;
+                            return Action::Push(position + 1);
+                        }
+                        false
+                    }
+                    // A repetition that gave nothing is where the relevant iterators ran out,
+                    // rather than a repetition that was completed.
+                    Event::Finished {
+                        failed: false,
+                        unmet: child_unmet,
+                    } if *count > 0 => {
+                        *completed += 1;
+                        *unmet |= child_unmet;
+                        return Action::Continue;
+                    }
+                    Event::Finished { failed, .. } => failed,
+                };
+                Action::Pop {
+                    failed,
+                    unmet: *unmet || !*zero_width && *completed < *minimum,
+                }
+            }
+            (Instr::Alternation { end }, FrameState::Alternation { child, committed }, event) => {
+                match event {
+                    Event::Continue if *child < *end => Action::Push(*child),
+                    Event::Continue => Action::Pop {
+                        failed: false,
+                        unmet: false,
+                    },
+                    Event::Finished { failed, unmet } => {
+                        let next = self.end(*child);
+                        if !*committed && next < *end {
+                            *child = next;
+                            Action::Push(next)
+                        } else {
+                            Action::Pop {
+                                failed,
+                                unmet: (*committed || next == *end) && unmet,
+                            }
+                        }
+                    }
+                }
+            }
+            (
+                Instr::Weighted { .. },
+                FrameState::Weighted {
+                    candidates,
+                    committed,
+                },
+                event,
+            ) => match event {
+                Event::Finished { failed, unmet } if *committed => Action::Pop { failed, unmet },
+                _ => Action::Choose(*candidates),
+            },
+            (Instr::Discard { .. }, FrameState::Discard, Event::Continue) => {
+                Action::Push(position + 1)
+            }
+            (
+                Instr::Conditional {
+                    source, negated, ..
+                },
+                FrameState::Conditional,
+                Event::Continue,
+            ) => {
+                let has_items = source.map_or(false, |index| !iters.is_exhausted(index));
+                if has_items != *negated {
+                    Action::Push(position + 1)
+                } else {
+                    Action::Pop {
+                        failed: false,
+                        unmet: false,
+                    }
+                }
+            }
+            (
+                _,
+                FrameState::Discard | FrameState::Conditional,
+                Event::Finished { failed, unmet },
+            ) => Action::Pop { failed, unmet },
+            (Instr::Anchor(source), _, _) => Action::Pop {
+                failed: source.map_or(false, |index| !iters.is_exhausted(index)),
+                unmet: false,
+            },
+            (_, FrameState::Taken(taken @ false), _) => {
+                *taken = true;
+                Action::Take
+            }
+            _ => Action::Pop {
+                failed: false,
+                unmet: false,
+            },
+        }
+    }
+
+    /// Takes an item for the Take, Class or Sorted instruction at `position`.
+    fn take<T, I>(&self, cursor: &mut Cursor, position: usize, iters: &mut Sources<T>) -> Option<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        match &self.instrs[position] {
+            Instr::Take(index) => iters.next(*index),
+            Instr::Class { indices, rotation } => {
+                let indices = indices
+                    .as_ref()
+                    .map(|indices| &self.indices[indices.clone()]);
+                iters.next_in_class(&mut cursor.rotations[*rotation], indices)
+            }
+            Instr::Sorted(indices) => iters.next_smallest(&self.indices[indices.clone()]),
+            _ => None,
+        }
+    }
+
+    /// Whether the Take, Class or Sorted instruction at `position` would take an item, without
+    /// taking it.
+    fn can_take<T>(&self, position: usize, iters: &mut Sources<T>) -> bool
+    where
+        T: Iterator,
+    {
+        let indices = match &self.instrs[position] {
+            Instr::Take(index) => return !iters.is_exhausted(*index),
+            Instr::Class { indices: None, .. } => {
+                return (0..iters.len()).any(|index| !iters.is_exhausted(index as isize))
+            }
+            Instr::Class {
+                indices: Some(indices),
+                ..
+            }
+            | Instr::Sorted(indices) => &self.indices[indices.clone()],
+            _ => return false,
+        };
+        indices.iter().any(|&index| !iters.is_exhausted(index))
+    }
+
+    /// Whether a minimum that wasn't met would count if the frame on top of the stack gave an
+    /// item. Giving an item counts the repetitions and commits the alternatives below it, so
+    /// any frame that holds an unmet minimum would pass it down to the first frame.
+    fn unmet_if_given(&self, cursor: &Cursor) -> bool {
+        cursor.frames.iter().any(|frame| {
+            matches!(
+                frame.state,
+                FrameState::Seq { unmet: true, .. } | FrameState::Repeat { unmet: true, .. }
+            )
+        })
+    }
+
+    /// Passes an item taken by the frame on top of the stack down to the frames below it.
+    fn give<I>(&self, cursor: &mut Cursor, item: I) -> Step<I> {
+        let mut step = Step::Item(item);
+        for frame in cursor.frames.iter_mut().rev().skip(1) {
+            match &mut frame.state {
+                FrameState::Repeat { count, .. } => *count += 1,
+                FrameState::Alternation { committed, .. }
+                | FrameState::Weighted { committed, .. } => *committed = true,
+                FrameState::Discard => step = Step::Discarded,
+                _ => {}
+            }
+        }
+        step
+    }
+}
+
+impl IMExIterator for Program {
+    type State = Cursor;
+
+    fn start(&self) -> Cursor {
+        let mut cursor = Cursor {
+            frames: Vec::with_capacity(self.depth),
+            candidates: Vec::with_capacity(self.alternatives.len()),
+            rotations: vec![0; self.classes],
+            unmet: false,
+            strict: false,
+        };
+        self.push(&mut cursor, 0);
+        cursor
+    }
+
+    fn step<T, I>(&self, cursor: &mut Cursor, iters: &mut Sources<T>) -> Step<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        let mut event = Event::Continue;
+        while let Some(frame) = cursor.frames.last_mut() {
+            let position = frame.position;
+            event = match self.act(frame, event, iters) {
+                Action::Continue => Event::Continue,
+                Action::Push(position) => {
+                    self.push(cursor, position);
+                    Event::Continue
+                }
+                Action::Choose(start) => match self.choose(cursor, start, iters) {
+                    Some(alternative) => {
+                        self.push(cursor, alternative);
+                        Event::Continue
+                    }
+                    None => {
+                        self.pop(cursor);
+                        Event::Finished {
+                            failed: false,
+                            unmet: false,
+                        }
+                    }
+                },
+                Action::Pop { failed, unmet } => {
+                    self.pop(cursor);
+                    cursor.unmet = unmet;
+                    Event::Finished { failed, unmet }
+                }
+                // Merging strictly, the run ends before an item is taken after a minimum that
+                // wasn't met. The iterator is only peeked if the minimum counts just when the
+                // item is given.
+                Action::Take
+                    if cursor.strict
+                        && (self.unmet_minimum(cursor)
+                            || self.unmet_if_given(cursor) && self.can_take(position, iters)) =>
+                {
+                    cursor.frames.clear();
+                    cursor.candidates.clear();
+                    cursor.unmet = true;
+                    return Step::Done;
+                }
+                Action::Take => match self.take(cursor, position, iters) {
+                    Some(item) => return self.give(cursor, item),
+                    None => {
+                        self.pop(cursor);
+                        Event::Finished {
+                            failed: false,
+                            unmet: false,
+                        }
+                    }
+                },
+            };
+        }
+        Step::Done
+    }
+
+    fn unmet_minimum(&self, cursor: &Cursor) -> bool {
+        if cursor.frames.is_empty() {
+            return cursor.unmet;
+        }
+        cursor.frames.iter().rev().fold(false, |unmet, frame| {
+            match (&self.instrs[frame.position], &frame.state) {
+                (_, FrameState::Seq { unmet: own, .. }) => *own || unmet,
+                (
+                    _,
+                    FrameState::Repeat {
+                        count, unmet: own, ..
+                    },
+                ) => *own || *count > 0 && unmet,
+                (Instr::Alternation { end }, FrameState::Alternation { child, committed }) => {
+                    (*committed || self.end(*child) == *end) && unmet
+                }
+                _ => unmet,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        tree::{TreeIterator, TreeSources},
+        *,
+    };
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        convert::TryFrom,
+        io::Result,
+    };
+
+    /// Counts the allocations made by each thread, so a test can check that it made none.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = Cell::new(0);
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    const IMEXES: &[&str] = &[
+        "",
+        "0110",
+        "(01)*",
+        "0(01){4}",
+        "(012){4}(122)*",
+        "01(10){3}",
+        "(0{2}1)+2?",
+        "(0{1,3}1{,2}2{2,})*",
+        "(0|1)*",
+        "(01|2)*1*",
+        "(0%3|1%1|2%1)*",
+        "((0%1|1%1)2|(1%1|2%1))*",
+        "[0-2]*",
+        "(.)*0",
+        "([0,2]1)*",
+        "<012>*",
+        "(<01>2)*",
+        "(~0{2}1)*",
+        "(~(01)2)*",
+        "((?0:01)(?!1:2))*",
+        "(0$1|1$2|2)*",
+        "(0(1$0)*2)*",
+        "(?<pair>01)(&pair)*2*",
+        "((?<a>0)(&a)1)*",
+    ];
+
+    fn sources<'a>(inputs: &[&'a str]) -> Sources<std::str::Chars<'a>> {
+        let mut iters = Sources::from(inputs.iter().map(|input| input.chars()).collect::<Vec<_>>());
+        iters.seed(11);
+        iters.order_by(Box::new(|a: &char, b: &char| a.cmp(b)));
+        iters
+    }
+
+    /// Runs `imex` on `inputs` with both the tree of the IMEx and its compiled Program, asserting
+    /// that every step, and whether a minimum is unmet after it, is the same.
+    fn assert_equivalent(imex: &str, inputs: &[&str]) -> Result<()> {
+        let tree = IMEx::try_from(imex)?;
+        let program = Program::compile(&tree);
+        let mut tree_state = tree.start();
+        let mut tree_iters = TreeSources::from(sources(inputs));
+        let mut cursor = program.start();
+        let mut program_iters = sources(inputs);
+
+        for step in 0..1000 {
+            let expected = Step::from(tree.step(&mut tree_state, &mut tree_iters));
+            let actual = program.step(&mut cursor, &mut program_iters);
+            assert_eq!(actual, expected, "{} on {:?}, step {}", imex, inputs, step);
+            assert_eq!(
+                program.unmet_minimum(&cursor),
+                tree.unmet_minimum(&tree_state),
+                "unmet minimum for {} on {:?}, step {}",
+                imex,
+                inputs,
+                step
+            );
+            if expected == Step::Done {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn program_matches_tree() -> Result<()> {
+        let inputs: &[&[&str]] = &[
+            &["", "", ""],
+            &["a", "", ""],
+            &["abc", "xy", "z"],
+            &["adgk", "beh", "cfij"],
+            &["aaaaaaaaa", "b", "cccc"],
+            &["", "bbbbbb", "cc"],
+        ];
+        for imex in IMEXES {
+            for inputs in inputs {
+                assert_equivalent(imex, inputs)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn program_matches_tree_on_a_million_items() -> Result<()> {
+        let left = "ab".repeat(250_000);
+        let right = "xyz".repeat(166_667);
+        for imex in &["(01)*", "(0{2}[0,1]|~1)*", "(.)*", "<01>*"] {
+            let tree = IMEx::try_from(*imex)?;
+            let program = Program::compile(&tree);
+            let mut tree_state = tree.start();
+            let mut tree_iters = TreeSources::from(sources(&[&left, &right]));
+            let mut cursor = program.start();
+            let mut program_iters = sources(&[&left, &right]);
+
+            let expected = std::iter::from_fn(|| tree.iterate(&mut tree_state, &mut tree_iters));
+            let actual = std::iter::from_fn(|| program.iterate(&mut cursor, &mut program_iters));
+            assert!(actual.eq(expected), "{}", imex);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stepping_program_doesnt_allocate() -> Result<()> {
+        for imex in IMEXES {
+            let program = Program::compile(&IMEx::try_from(*imex)?);
+            let mut cursor = program.start();
+            let mut iters = sources(&["abcdefghij", "klmnopqrst", "uvwxyz"]);
+
+            let before = allocations();
+            while program.step(&mut cursor, &mut iters) != Step::Done {}
+            assert_eq!(allocations(), before, "{}", imex);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn program_is_flat() -> Result<()> {
+        let program = Program::compile(&IMEx::try_from("(01)*")?);
+
+        assert_eq!(
+            program.instrs,
+            vec![
+                Instr::Seq { end: 7 },
+                Instr::Repeat {
+                    end: 7,
+                    minimum: 0,
+                    maximum: None,
+                    zero_width: false
+                },
+                Instr::Seq { end: 7 },
+                Instr::Repeat {
+                    end: 5,
+                    minimum: 1,
+                    maximum: Some(1),
+                    zero_width: false
+                },
+                Instr::Take(0),
+                Instr::Repeat {
+                    end: 7,
+                    minimum: 1,
+                    maximum: Some(1),
+                    zero_width: false
+                },
+                Instr::Take(1),
+            ]
+        );
+        assert_eq!(program.depth, 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,1176 @@
+//! The tree interpreter, which merges by walking a parsed [`IMEx`](../../expression/struct.IMEx.html)
+//! directly, keeping a State for each value in progress. It was replaced by the compiled
+//! [`Program`](../struct.Program.html), and is only kept for tests, as the oracle that a Program
+//! has to agree with.
+
+use crate::{
+    expression::{
+        Conditional, IMEx, IMExVal, QuantifiedIMExVal, SortedMerge, SourceClass, WeightedChoice,
+    },
+    Sources, Step,
+};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
+
+/// The result of a single step of a [`TreeIterator`](./trait.TreeIterator.html), like a
+/// [`Step`](../../enum.Step.html), which a failed zero-width assertion can also give.
+#[derive(PartialEq, Debug)]
+pub enum TreeStep<I> {
+    /// An item was taken from the iters, and is part of the merge.
+    Item(I),
+    /// An item was taken from the iters, but was discarded.
+    Discarded,
+    /// No item was taken from the iters.
+    Done,
+    /// A zero-width assertion, like an anchor, failed. The rest of the enclosing IMEx is skipped.
+    Failed,
+}
+
+impl<I> From<Option<I>> for TreeStep<I> {
+    fn from(item: Option<I>) -> TreeStep<I> {
+        match item {
+            Some(item) => TreeStep::Item(item),
+            None => TreeStep::Done,
+        }
+    }
+}
+
+/// The step that a Program gives for a step of the tree. An IMEx skips the rest of itself when
+/// a value in it fails, so outside the tree a failure is the end of the IMEx.
+impl<I> From<TreeStep<I>> for Step<I> {
+    fn from(step: TreeStep<I>) -> Step<I> {
+        match step {
+            TreeStep::Item(item) => Step::Item(item),
+            TreeStep::Discarded => Step::Discarded,
+            TreeStep::Done | TreeStep::Failed => Step::Done,
+        }
+    }
+}
+
+/// The iterators being merged by the tree interpreter, along with the rotations of its source
+/// classes, which a Program keeps in its Cursor instead.
+pub struct TreeSources<T>
+where
+    T: Iterator,
+{
+    sources: Sources<T>,
+    rotations: HashMap<usize, usize>,
+}
+
+impl<T> TreeSources<T>
+where
+    T: Iterator,
+{
+    /// Returns the rotation stored for `key`, or 0 if none has been stored.
+    fn rotation(&self, key: usize) -> usize {
+        self.rotations.get(&key).copied().unwrap_or(0)
+    }
+
+    /// Stores the rotation for `key`, so it carries over to the next time it is needed.
+    fn set_rotation(&mut self, key: usize, rotation: usize) {
+        self.rotations.insert(key, rotation);
+    }
+}
+
+impl<T> From<Sources<T>> for TreeSources<T>
+where
+    T: Iterator,
+{
+    fn from(sources: Sources<T>) -> TreeSources<T> {
+        TreeSources {
+            sources,
+            rotations: HashMap::new(),
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for TreeSources<T>
+where
+    T: Iterator,
+{
+    fn from(iters: Vec<T>) -> TreeSources<T> {
+        TreeSources::from(Sources::from(iters))
+    }
+}
+
+impl<T> Deref for TreeSources<T>
+where
+    T: Iterator,
+{
+    type Target = Sources<T>;
+
+    fn deref(&self) -> &Sources<T> {
+        &self.sources
+    }
+}
+
+impl<T> DerefMut for TreeSources<T>
+where
+    T: Iterator,
+{
+    fn deref_mut(&mut self) -> &mut Sources<T> {
+        &mut self.sources
+    }
+}
+
+/// Like an [`IMExIterator`](../../trait.IMExIterator.html), for the values of the tree, which
+/// keep their own State.
+pub trait TreeIterator {
+    /// The progress of a single iteration over this TreeIterator.
+    type State;
+
+    /// Returns the State of an iteration that hasn't taken anything yet.
+    fn start(&self) -> Self::State;
+
+    /// Takes a single item from the iters, and tells whether that item is part of the merge.
+    fn step<T, I>(&self, state: &mut Self::State, iters: &mut TreeSources<T>) -> TreeStep<I>
+    where
+        T: Iterator<Item = I>;
+
+    /// Steps past discarded items until an item that is part of the merge is found.
+    fn iterate<T, I>(&self, state: &mut Self::State, iters: &mut TreeSources<T>) -> Option<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        loop {
+            match self.step(state, iters) {
+                TreeStep::Item(item) => return Some(item),
+                TreeStep::Discarded => continue,
+                TreeStep::Done | TreeStep::Failed => return None,
+            }
+        }
+    }
+
+    /// Whether a quantifier within this TreeIterator has finished without meeting its minimum
+    /// number of repetitions.
+    fn unmet_minimum(&self, _state: &Self::State) -> bool {
+        false
+    }
+}
+
+/// The progress of an iteration over an [`IMEx`](../../expression/struct.IMEx.html): the
+/// position of the current value, and the progress of the iteration over that value.
+#[derive(Debug)]
+pub struct IMExState {
+    position: usize,
+    current: Option<Box<QuantifiedIMExValState>>,
+    unmet: bool,
+}
+
+impl TreeIterator for IMEx {
+    type State = IMExState;
+
+    fn start(&self) -> IMExState {
+        IMExState {
+            position: 0,
+            current: None,
+            unmet: false,
+        }
+    }
+
+    fn step<T, I>(&self, state: &mut IMExState, iters: &mut TreeSources<T>) -> TreeStep<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        while let Some(val) = self.vals.get(state.position) {
+            let current = state.current.get_or_insert_with(|| Box::new(val.start()));
+            match val.step(current, iters) {
+                TreeStep::Done => {
+                    state.unmet |= val.unmet_minimum(current);
+                    state.current = None;
+                    state.position += 1;
+                }
+                TreeStep::Failed => {
+                    state.current = None;
+                    state.position = self.vals.len();
+                    return TreeStep::Done;
+                }
+                step => return step,
+            }
+        }
+        TreeStep::Done
+    }
+
+    fn unmet_minimum(&self, state: &IMExState) -> bool {
+        state.unmet
+            || match (&state.current, self.vals.get(state.position)) {
+                (Some(current), Some(val)) => val.unmet_minimum(current),
+                _ => false,
+            }
+    }
+}
+
+/// The progress of an iteration over a
+/// [`QuantifiedIMExVal`](../../expression/struct.QuantifiedIMExVal.html): the progress of the
+/// current repetition, how many steps it has taken, and how many repetitions were started and
+/// completed.
+#[derive(Debug)]
+pub struct QuantifiedIMExValState {
+    current: Option<IMExValState>,
+    count: u32,
+    started: usize,
+    completed: usize,
+    finished: bool,
+    unmet: bool,
+}
+
+/// If the quantifier of `val` allows another repetition, starts repeating its value and returns
+/// true. Otherwise, returns false.
+fn repeat(val: &QuantifiedIMExVal, state: &mut QuantifiedIMExValState) -> bool {
+    let allowed = !state.finished
+        && val
+            .get_quantifier()
+            .maximum()
+            .map_or(true, |max| state.started < max);
+    if allowed {
+        state.current = Some(val.get_val().start());
+        state.count = 0;
+        state.started += 1;
+    }
+    allowed
+}
+
+/// Stops repeating the value of `val`, and checks whether enough repetitions were completed to
+/// satisfy the minimum of its quantifier. Zero-width values never give items, so they have no
+/// minimum to satisfy.
+fn finish(val: &QuantifiedIMExVal, state: &mut QuantifiedIMExValState) {
+    state.finished = true;
+    state.current = None;
+    state.unmet |=
+        !val.get_val().is_zero_width() && state.completed < val.get_quantifier().minimum();
+}
+
+impl TreeIterator for QuantifiedIMExVal {
+    type State = QuantifiedIMExValState;
+
+    fn start(&self) -> QuantifiedIMExValState {
+        QuantifiedIMExValState {
+            current: None,
+            count: 0,
+            started: 0,
+            completed: 0,
+            finished: false,
+            unmet: false,
+        }
+    }
+
+    fn step<T, I>(
+        &self,
+        state: &mut QuantifiedIMExValState,
+        iters: &mut TreeSources<T>,
+    ) -> TreeStep<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        loop {
+            let current = match &mut state.current {
+                Some(current) => current,
+                None => {
+                    if !repeat(self, state) {
+                        finish(self, state);
+                        return TreeStep::Done;
+                    }
+                    continue;
+                }
+            };
+            match self.get_val().step(current, iters) {
+                TreeStep::Done => {
+                    // A repetition that gave nothing is where the relevant iterators ran out,
+                    // rather than a repetition that was completed.
+                    if state.count > 0 {
+                        state.completed += 1;
+                        state.unmet |= self.get_val().unmet_minimum(current);
+                    }
+                    if !(state.count > 0 && repeat(self, state)) {
+                        finish(self, state);
+                        return TreeStep::Done;
+                    }
+                }
+                TreeStep::Failed => {
+                    finish(self, state);
+                    return TreeStep::Failed;
+                }
+                step => {
+                    state.count += 1;
+                    return step;
+                }
+            }
+        }
+    }
+
+    fn unmet_minimum(&self, state: &QuantifiedIMExValState) -> bool {
+        state.unmet
+            || state.count > 0
+                && state
+                    .current
+                    .as_ref()
+                    .map_or(false, |current| self.get_val().unmet_minimum(current))
+    }
+}
+
+/// The progress of an iteration over an [`IMExVal`](../../expression/enum.IMExVal.html). Values
+/// that take at most one item only keep track of whether they've taken it.
+#[derive(Debug)]
+pub enum IMExValState {
+    Taken(bool),
+    Group(IMExState),
+    Alternation {
+        position: usize,
+        imex: Option<IMExState>,
+        committed: bool,
+    },
+    Weighted(WeightedChoiceState),
+    Discard(Box<IMExValState>),
+    Conditional(ConditionalState),
+}
+
+impl TreeIterator for IMExVal {
+    type State = IMExValState;
+
+    fn start(&self) -> IMExValState {
+        match self {
+            IMExVal::Group(imex) | IMExVal::NamedGroup(_, imex) | IMExVal::Call(_, imex) => {
+                IMExValState::Group(imex.start())
+            }
+            IMExVal::Alternation(imexes) => IMExValState::Alternation {
+                position: 0,
+                imex: imexes.first().map(IMEx::start),
+                committed: false,
+            },
+            IMExVal::Weighted(choice) => IMExValState::Weighted(choice.start()),
+            IMExVal::Discard(val) => IMExValState::Discard(Box::new(val.start())),
+            IMExVal::Conditional(conditional) => IMExValState::Conditional(conditional.start()),
+            _ => IMExValState::Taken(false),
+        }
+    }
+
+    fn step<T, I>(&self, state: &mut IMExValState, iters: &mut TreeSources<T>) -> TreeStep<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        match (self, state) {
+            (IMExVal::Single(index), IMExValState::Taken(taken)) => {
+                if *taken {
+                    return TreeStep::Done;
+                }
+                *taken = true;
+                iters.next(*index).into()
+            }
+            (IMExVal::Named(_), _) => TreeStep::Done,
+            (
+                IMExVal::Group(imex) | IMExVal::NamedGroup(_, imex) | IMExVal::Call(_, imex),
+                IMExValState::Group(state),
+            ) => imex.step(state, iters),
+            (
+                IMExVal::Alternation(imexes),
+                IMExValState::Alternation {
+                    position,
+                    imex: Some(state),
+                    committed,
+                },
+            ) => loop {
+                match imexes[*position].step(state, iters) {
+                    TreeStep::Done if !*committed && *position + 1 < imexes.len() => {
+                        *position += 1;
+                        *state = imexes[*position].start();
+                    }
+                    TreeStep::Done => return TreeStep::Done,
+                    step => {
+                        *committed = true;
+                        return step;
+                    }
+                }
+            },
+            (IMExVal::Weighted(choice), IMExValState::Weighted(state)) => choice.step(state, iters),
+            (IMExVal::Class(class), IMExValState::Taken(taken)) => class.step(taken, iters),
+            (IMExVal::Sorted(sorted), IMExValState::Taken(taken)) => sorted.step(taken, iters),
+            (IMExVal::Discard(val), IMExValState::Discard(state)) => match val.step(state, iters) {
+                TreeStep::Item(_) => TreeStep::Discarded,
+                step => step,
+            },
+            (IMExVal::Conditional(conditional), IMExValState::Conditional(state)) => {
+                conditional.step(state, iters)
+            }
+            (IMExVal::Anchor(val), _) => match val.source_index() {
+                Some(index) if !iters.is_exhausted(index) => TreeStep::Failed,
+                _ => TreeStep::Done,
+            },
+            _ => TreeStep::Done,
+        }
+    }
+
+    fn unmet_minimum(&self, state: &IMExValState) -> bool {
+        match (self, state) {
+            (
+                IMExVal::Group(imex) | IMExVal::NamedGroup(_, imex) | IMExVal::Call(_, imex),
+                IMExValState::Group(state),
+            ) => imex.unmet_minimum(state),
+            (
+                IMExVal::Alternation(imexes),
+                IMExValState::Alternation {
+                    position,
+                    imex: Some(state),
+                    committed,
+                },
+            ) => {
+                (*committed || *position + 1 == imexes.len())
+                    && imexes[*position].unmet_minimum(state)
+            }
+            (IMExVal::Weighted(choice), IMExValState::Weighted(state)) => {
+                choice.unmet_minimum(state)
+            }
+            (IMExVal::Discard(val), IMExValState::Discard(state)) => val.unmet_minimum(state),
+            (IMExVal::Conditional(conditional), IMExValState::Conditional(state)) => {
+                conditional.unmet_minimum(state)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The progress of an iteration over a
+/// [`WeightedChoice`](../../expression/struct.WeightedChoice.html): the alternatives that can
+/// still be picked, and the picked alternative, along with the progress of the iteration over
+/// it and whether it has taken anything.
+#[derive(Debug)]
+pub struct WeightedChoiceState {
+    candidates: Vec<usize>,
+    chosen: Option<(usize, IMExState, bool)>,
+}
+
+/// Picks one of the remaining candidates of `choice` at random, by weight, and removes it from
+/// the candidates. Returns None if there are no candidates left.
+fn choose<T>(
+    choice: &WeightedChoice,
+    state: &mut WeightedChoiceState,
+    iters: &mut TreeSources<T>,
+) -> Option<usize>
+where
+    T: Iterator,
+{
+    let alternatives = choice.get_alternatives();
+    let total = state.candidates.iter().fold(0u64, |total, &c| {
+        total.saturating_add(alternatives[c].1 as u64)
+    });
+    let mut roll = iters.random_below(total);
+    for (position, &candidate) in state.candidates.iter().enumerate() {
+        let weight = alternatives[candidate].1 as u64;
+        if roll < weight {
+            state.candidates.remove(position);
+            return Some(candidate);
+        }
+        roll -= weight;
+    }
+    None
+}
+
+impl TreeIterator for WeightedChoice {
+    type State = WeightedChoiceState;
+
+    fn start(&self) -> WeightedChoiceState {
+        WeightedChoiceState {
+            candidates: (0..self.get_alternatives().len()).collect(),
+            chosen: None,
+        }
+    }
+
+    fn step<T, I>(&self, state: &mut WeightedChoiceState, iters: &mut TreeSources<T>) -> TreeStep<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        loop {
+            match &mut state.chosen {
+                Some((chosen, imex, committed)) => {
+                    match self.get_alternatives()[*chosen].0.step(imex, iters) {
+                        TreeStep::Done if !*committed => state.chosen = None,
+                        step => {
+                            *committed = true;
+                            return step;
+                        }
+                    }
+                }
+                None => match choose(self, state, iters) {
+                    Some(chosen) => {
+                        let imex = self.get_alternatives()[chosen].0.start();
+                        state.chosen = Some((chosen, imex, false));
+                    }
+                    None => return TreeStep::Done,
+                },
+            }
+        }
+    }
+
+    fn unmet_minimum(&self, state: &WeightedChoiceState) -> bool {
+        match &state.chosen {
+            Some((chosen, imex, _)) => self.get_alternatives()[*chosen].0.unmet_minimum(imex),
+            None => false,
+        }
+    }
+}
+
+/// The progress of an iteration over a
+/// [`Conditional`](../../expression/struct.Conditional.html): whether the check passed, once it
+/// has been made, and the progress of the iteration over the inner IMEx.
+#[derive(Debug)]
+pub struct ConditionalState {
+    passed: Option<bool>,
+    imex: IMExState,
+}
+
+impl TreeIterator for Conditional {
+    type State = ConditionalState;
+
+    fn start(&self) -> ConditionalState {
+        ConditionalState {
+            passed: None,
+            imex: self.get_imex().start(),
+        }
+    }
+
+    fn step<T, I>(&self, state: &mut ConditionalState, iters: &mut TreeSources<T>) -> TreeStep<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        let passed = match state.passed {
+            Some(passed) => passed,
+            None => {
+                let has_items = match self.get_source().source_index() {
+                    Some(index) => !iters.is_exhausted(index),
+                    None => false,
+                };
+                *state.passed.insert(has_items != self.is_negated())
+            }
+        };
+        if passed {
+            self.get_imex().step(&mut state.imex, iters)
+        } else {
+            TreeStep::Done
+        }
+    }
+
+    fn unmet_minimum(&self, state: &ConditionalState) -> bool {
+        state.passed == Some(true) && self.get_imex().unmet_minimum(&state.imex)
+    }
+}
+
+impl TreeIterator for SourceClass {
+    /// Whether an item has been taken.
+    type State = bool;
+
+    fn start(&self) -> bool {
+        false
+    }
+
+    fn step<T, I>(&self, taken: &mut bool, iters: &mut TreeSources<T>) -> TreeStep<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        if *taken {
+            return TreeStep::Done;
+        }
+        *taken = true;
+
+        // The rotation belongs to this SourceClass, so it's kept under its address, which
+        // doesn't change while it's being iterated.
+        let key = self as *const SourceClass as usize;
+        let mut rotation = iters.rotation(key);
+        let item = iters.next_in_class(&mut rotation, self.get_indices());
+        iters.set_rotation(key, rotation);
+        item.into()
+    }
+}
+
+impl TreeIterator for SortedMerge {
+    /// Whether an item has been taken.
+    type State = bool;
+
+    fn start(&self) -> bool {
+        false
+    }
+
+    fn step<T, I>(&self, taken: &mut bool, iters: &mut TreeSources<T>) -> TreeStep<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        if *taken {
+            return TreeStep::Done;
+        }
+        *taken = true;
+
+        let indices = self
+            .get_sources()
+            .iter()
+            .filter_map(IMExVal::source_index)
+            .collect::<Vec<isize>>();
+        iters.next_smallest(&indices).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::Quantifier;
+    use std::{convert::TryFrom, io::Result};
+
+    #[test]
+    fn single_iterates_once() {
+        let imex_val = IMExVal::Single(1);
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('a'));
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+    }
+
+    #[test]
+    fn negative_single_counts_from_last() {
+        let imex_val = IMExVal::Single(-1);
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars(), "xyz".chars()]);
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('x'));
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+    }
+
+    #[test]
+    fn out_of_range_negative_single_gives_none() {
+        let imex_val = IMExVal::Single(-3);
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+    }
+
+    #[test]
+    fn unresolved_named_gives_none() {
+        let imex_val = IMExVal::Named("a".to_string());
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+    }
+
+    #[test]
+    fn resolved_named_iterates_once() -> Result<()> {
+        let imex = IMEx::try_from("b")?.resolve_names(&["a", "b"])?;
+        let imex_val = imex.vals[0].get_val().clone();
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val, IMExVal::Single(1));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+        Ok(())
+    }
+
+    #[test]
+    fn alternation_falls_back_to_next_alternative() -> Result<()> {
+        let imex_val = IMExVal::Alternation(vec![IMEx::try_from("2")?, IMEx::try_from("01")?]);
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars(), "".chars()]);
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('1'));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+        Ok(())
+    }
+
+    #[test]
+    fn alternation_sticks_to_first_alternative_with_items() -> Result<()> {
+        let imex_val = IMExVal::Alternation(vec![IMEx::try_from("01")?, IMEx::try_from("1")?]);
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["1".chars(), "".chars()]);
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('1'));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+        Ok(())
+    }
+
+    #[test]
+    fn alternation_without_items_gives_none() -> Result<()> {
+        let imex_val = IMExVal::Alternation(vec![IMEx::try_from("0")?, IMEx::try_from("")?]);
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+        Ok(())
+    }
+
+    #[test]
+    fn discard_steps_without_items() {
+        let imex_val = IMExVal::Discard(Box::new(IMExVal::Single(1)));
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val.step(&mut state, &mut iters), TreeStep::Discarded);
+        assert_eq!(imex_val.step(&mut state, &mut iters), TreeStep::Done);
+        assert_eq!(iters.next(1), Some('b'));
+    }
+
+    #[test]
+    fn anchor_passes_on_exhausted_iter() {
+        let imex_val = IMExVal::Anchor(Box::new(IMExVal::Single(0)));
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val.step(&mut state, &mut iters), TreeStep::Done);
+    }
+
+    #[test]
+    fn anchor_fails_on_unexhausted_iter() {
+        let imex_val = IMExVal::Anchor(Box::new(IMExVal::Single(1)));
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val.step(&mut state, &mut iters), TreeStep::Failed);
+        assert_eq!(iters.next(1), Some('a'));
+    }
+
+    #[test]
+    fn group_iterates_over_imex() -> Result<()> {
+        let imex_val = IMExVal::Group(IMEx::try_from("01*")?);
+        let mut state = imex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('1'));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('b'));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), Some('c'));
+        assert_eq!(imex_val.iterate(&mut state, &mut iters), None);
+        Ok(())
+    }
+
+    #[test]
+    fn iterating_new_qimex_val_with_zero_quantifier_gives_none() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(0));
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+    }
+
+    #[test]
+    fn iterating_qimex_val_with_three_quantifier_repeats_thrice() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(3));
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abcde".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('b'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('c'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+    }
+
+    #[test]
+    fn exhausting_imex_val_before_repeats_copmlete_gives_none() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(5));
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('b'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('c'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+    }
+
+    #[test]
+    fn iterating_qimex_val_with_range_quantifier_stops_at_max() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Range(1, Some(2)));
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abcde".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('b'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+        assert!(!qimex_val.unmet_minimum(&state));
+    }
+
+    #[test]
+    fn exhausting_imex_val_before_min_is_unmet() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Range(4, None));
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('b'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('c'));
+        assert!(!qimex_val.unmet_minimum(&state));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+        assert!(qimex_val.unmet_minimum(&state));
+    }
+
+    #[test]
+    fn iterating_qimex_val_with_optional_quantifier_repeats_once() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Optional);
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+        assert!(!qimex_val.unmet_minimum(&state));
+    }
+
+    #[test]
+    fn iterating_qimex_val_with_at_least_once_quantifier_repeats_until_exhausted() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::AtLeastOnce);
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "ab".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('b'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+        assert!(!qimex_val.unmet_minimum(&state));
+    }
+
+    #[test]
+    fn at_least_once_quantifier_without_any_repetitions_is_unmet() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::AtLeastOnce);
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+        assert!(qimex_val.unmet_minimum(&state));
+    }
+
+    #[test]
+    fn optional_quantifier_without_any_repetitions_is_met() {
+        let qimex_val = QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Optional);
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+        assert!(!qimex_val.unmet_minimum(&state));
+    }
+
+    #[test]
+    fn exhausted_repetition_doesnt_count_towards_unmet_minimum() -> Result<()> {
+        let qimex_val = QuantifiedIMExVal::new(
+            IMExVal::Group(IMEx::try_from("0{2,}")?),
+            Quantifier::Infinite,
+        );
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["1234".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('1'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('2'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('3'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('4'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+        assert!(!qimex_val.unmet_minimum(&state));
+
+        Ok(())
+    }
+
+    #[test]
+    fn group_imex_val_completes_inner_iteration_first() -> Result<()> {
+        let qimex_val = QuantifiedIMExVal::new(
+            IMExVal::Group(IMEx::try_from("01*")?),
+            Quantifier::Finite(2),
+        );
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('1'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('b'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('c'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('2'));
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn state_counts_iterations() -> Result<()> {
+        let qimex_val =
+            QuantifiedIMExVal::new(IMExVal::Group(IMEx::try_from("0*")?), Quantifier::Finite(1));
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars()]);
+
+        assert_eq!(state.count, 0);
+
+        qimex_val.iterate(&mut state, &mut iters);
+        assert_eq!(state.count, 1);
+
+        qimex_val.iterate(&mut state, &mut iters);
+        assert_eq!(state.count, 2);
+
+        qimex_val.iterate(&mut state, &mut iters);
+        assert_eq!(state.count, 3);
+
+        qimex_val.iterate(&mut state, &mut iters);
+        assert_eq!(state.count, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn state_counts_discarded_iterations() -> Result<()> {
+        let qimex_val = QuantifiedIMExVal::new(
+            IMExVal::Group(IMEx::try_from("~0{2}0")?),
+            Quantifier::Finite(1),
+        );
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars()]);
+
+        assert_eq!(
+            qimex_val.step(&mut state, &mut iters),
+            TreeStep::<char>::Discarded
+        );
+        assert_eq!(state.count, 1);
+
+        assert_eq!(qimex_val.iterate(&mut state, &mut iters), Some('3'));
+        assert_eq!(state.count, 3);
+
+        assert_eq!(
+            qimex_val.step(&mut state, &mut iters),
+            TreeStep::<char>::Done
+        );
+        assert_eq!(state.count, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn state_wont_count_past_zero_on_empty_imex() -> Result<()> {
+        let qimex_val =
+            QuantifiedIMExVal::new(IMExVal::Group(IMEx::try_from("")?), Quantifier::Finite(1));
+        let mut state = qimex_val.start();
+        let mut iters = TreeSources::from(vec!["123".chars()]);
+
+        assert_eq!(state.count, 0);
+
+        qimex_val.iterate(&mut state, &mut iters);
+        assert_eq!(state.count, 0);
+
+        qimex_val.iterate(&mut state, &mut iters);
+        assert_eq!(state.count, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn weighted_choice_iterates_one_alternative() -> Result<()> {
+        let choice =
+            WeightedChoice::new(vec![(IMEx::try_from("00")?, 1), (IMEx::try_from("11")?, 1)]);
+        let mut state = choice.start();
+        let mut iters = TreeSources::from(vec!["ab".chars(), "xy".chars()]);
+
+        let taken = (0..3)
+            .filter_map(|_| choice.iterate(&mut state, &mut iters))
+            .collect::<String>();
+
+        assert!(taken == "ab" || taken == "xy");
+        Ok(())
+    }
+
+    #[test]
+    fn weighted_choice_falls_back_from_exhausted_alternative() -> Result<()> {
+        let choice = WeightedChoice::new(vec![
+            (IMEx::try_from("0")?, 1000),
+            (IMEx::try_from("1")?, 1),
+        ]);
+        let mut iters = TreeSources::from(vec!["".chars(), "xyz".chars()]);
+
+        let taken = (0..4)
+            .filter_map(|_| choice.iterate(&mut choice.start(), &mut iters))
+            .collect::<String>();
+
+        assert_eq!(taken, "xyz");
+        Ok(())
+    }
+
+    #[test]
+    fn weighted_choice_without_items_gives_none() -> Result<()> {
+        let choice =
+            WeightedChoice::new(vec![(IMEx::try_from("0")?, 3), (IMEx::try_from("1")?, 5)]);
+        let mut state = choice.start();
+        let mut iters = TreeSources::from(vec!["".chars(), "".chars()]);
+
+        assert_eq!(choice.iterate(&mut state, &mut iters), None);
+        Ok(())
+    }
+
+    #[test]
+    fn weighted_choice_follows_weights() -> Result<()> {
+        let choice =
+            WeightedChoice::new(vec![(IMEx::try_from("0")?, 70), (IMEx::try_from("1")?, 30)]);
+        let mut iters = TreeSources::from(vec![
+            std::iter::repeat('a').take(10000),
+            std::iter::repeat('b').take(10000),
+        ]);
+        iters.seed(7);
+
+        let firsts = (0..10000)
+            .filter_map(|_| choice.iterate(&mut choice.start(), &mut iters))
+            .filter(|c| *c == 'a')
+            .count();
+
+        assert!(firsts > 6800 && firsts < 7200, "{} of 10000", firsts);
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_with_items_iterates_over_imex() -> Result<()> {
+        let conditional = Conditional::new(IMExVal::Single(0), false, IMEx::try_from("01")?);
+        let mut state = conditional.start();
+        let mut iters = TreeSources::from(vec!["1".chars(), "abc".chars()]);
+
+        assert_eq!(conditional.iterate(&mut state, &mut iters), Some('1'));
+        assert_eq!(conditional.iterate(&mut state, &mut iters), Some('a'));
+        assert_eq!(conditional.iterate(&mut state, &mut iters), None);
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_without_items_gives_none() -> Result<()> {
+        let conditional = Conditional::new(IMExVal::Single(0), false, IMEx::try_from("1")?);
+        let mut state = conditional.start();
+        let mut iters = TreeSources::from(vec!["".chars(), "abc".chars()]);
+
+        assert_eq!(conditional.iterate(&mut state, &mut iters), None);
+        assert_eq!(iters.next(1), Some('a'));
+        Ok(())
+    }
+
+    #[test]
+    fn negated_conditional_iterates_only_when_exhausted() -> Result<()> {
+        let conditional = Conditional::new(IMExVal::Single(-1), true, IMEx::try_from("0")?);
+        let mut state = conditional.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "".chars()]);
+
+        assert_eq!(conditional.iterate(&mut state, &mut iters), Some('1'));
+
+        let conditional = Conditional::new(IMExVal::Single(0), true, IMEx::try_from("0")?);
+        let mut state = conditional.start();
+        assert_eq!(conditional.iterate(&mut state, &mut iters), None);
+        Ok(())
+    }
+
+    #[test]
+    fn class_iterates_once() {
+        let class = SourceClass::new(vec![1, 0]);
+        let mut taken = class.start();
+        let mut iters = TreeSources::from(vec!["123".chars(), "abc".chars()]);
+
+        assert_eq!(class.iterate(&mut taken, &mut iters), Some('a'));
+        assert_eq!(class.iterate(&mut taken, &mut iters), None);
+    }
+
+    #[test]
+    fn class_skips_exhausted_iterators() {
+        let class = SourceClass::new(vec![0, 2, -1]);
+        let mut taken = class.start();
+        let mut iters =
+            TreeSources::from(vec!["".chars(), "abc".chars(), "".chars(), "xyz".chars()]);
+
+        assert_eq!(class.iterate(&mut taken, &mut iters), Some('x'));
+    }
+
+    #[test]
+    fn class_rotation_is_shared_between_iterations() {
+        let class = SourceClass::new(vec![0, 1, 2]);
+        let mut iters = TreeSources::from(vec!["12".chars(), "".chars(), "xyz".chars()]);
+
+        let taken = (0..6)
+            .filter_map(|_| class.iterate(&mut class.start(), &mut iters))
+            .collect::<String>();
+
+        assert_eq!(taken, "1x2yz");
+    }
+
+    #[test]
+    fn any_class_rotates_through_every_iterator() {
+        let class = SourceClass::any();
+        let mut iters =
+            TreeSources::from(vec!["12".chars(), "".chars(), "xyz".chars(), "a".chars()]);
+
+        let taken = (0..8)
+            .filter_map(|_| class.iterate(&mut class.start(), &mut iters))
+            .collect::<String>();
+
+        assert_eq!(taken, "1xa2yz");
+    }
+
+    #[test]
+    fn any_class_without_iters_gives_none() {
+        let class = SourceClass::any();
+        let mut taken = class.start();
+        let mut iters: TreeSources<std::str::Chars> = TreeSources::from(vec![]);
+
+        assert_eq!(class.iterate(&mut taken, &mut iters), None);
+    }
+
+    #[test]
+    fn class_without_items_gives_none() {
+        let class = SourceClass::new(vec![0, 5]);
+        let mut taken = class.start();
+        let mut iters = TreeSources::from(vec!["".chars(), "abc".chars()]);
+
+        assert_eq!(class.iterate(&mut taken, &mut iters), None);
+    }
+
+    fn singles(indices: &[isize]) -> Vec<IMExVal> {
+        indices
+            .iter()
+            .map(|index| IMExVal::Single(*index))
+            .collect()
+    }
+
+    #[test]
+    fn sorted_merge_gives_smallest_head() {
+        let sorted = SortedMerge::new(singles(&[0, 1, 2]));
+        let mut iters = TreeSources::from(vec!["adg".chars(), "beh".chars(), "cf".chars()]);
+        iters.order_by(Box::new(|a: &char, b: &char| a.cmp(b)));
+
+        let taken = (0..10)
+            .filter_map(|_| sorted.iterate(&mut sorted.start(), &mut iters))
+            .collect::<String>();
+
+        assert_eq!(taken, "abcdefgh");
+    }
+
+    #[test]
+    fn sorted_merge_ties_go_to_first_listed() {
+        let sorted = SortedMerge::new(singles(&[1, 0]));
+        let mut iters = TreeSources::from(vec![
+            vec![(1, 'a'), (2, 'b')].into_iter(),
+            vec![(1, 'x'), (3, 'y')].into_iter(),
+        ]);
+        iters.order_by(Box::new(|a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0)));
+
+        let taken = (0..5)
+            .filter_map(|_| sorted.iterate(&mut sorted.start(), &mut iters))
+            .map(|(_, c)| c)
+            .collect::<String>();
+
+        assert_eq!(taken, "xaby");
+    }
+
+    #[test]
+    fn sorted_merge_without_comparator_takes_first_listed() {
+        let sorted = SortedMerge::new(singles(&[1, 0]));
+        let mut iters = TreeSources::from(vec!["ab".chars(), "c".chars()]);
+
+        let taken = (0..4)
+            .filter_map(|_| sorted.iterate(&mut sorted.start(), &mut iters))
+            .collect::<String>();
+
+        assert_eq!(taken, "cab");
+    }
+
+    #[test]
+    fn sorted_merge_iterates_once() {
+        let sorted = SortedMerge::new(singles(&[0, -1]));
+        let mut taken = sorted.start();
+        let mut iters = TreeSources::from(vec!["b".chars(), "a".chars()]);
+        iters.order_by(Box::new(|a: &char, b: &char| b.cmp(a)));
+
+        assert_eq!(sorted.iterate(&mut taken, &mut iters), Some('b'));
+        assert_eq!(sorted.iterate(&mut taken, &mut iters), None);
+    }
+}
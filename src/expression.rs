@@ -0,0 +1,112 @@
+//! This module contains objects for representing an IMEx.
+pub use imex_syntax::*;
+
+// The IMEx is parsed, printed and analysed by imex-syntax, which has its own tests. These tests
+// check it against the merges that imex performs.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IMExIter, Pattern};
+    use imex_syntax::random::{self, Numbers};
+    use std::{convert::TryFrom, io::Result, iter};
+
+    #[test]
+    fn bad_chars_fail_to_merge() {
+        // Letters parse as iterator names, which fail unless the iterators are named.
+        assert!(IMExIter::new(vec!["".chars()], "0O0").is_err());
+
+        assert!(IMExIter::new(vec!["".chars()], "123*4{5}(x)*").is_err());
+    }
+
+    #[test]
+    fn max_items_bounds_merges_of_infinite_iterators() -> Result<()> {
+        for imex_str in &[
+            "0{3}1",
+            "(01{2})?(2|34)",
+            "(0%1|1{3}%9){2,4}",
+            "~0{5}1$2(?3:4)",
+            "(?!0:1)(0|1){2}",
+            "<01>{4}.{3}[1,2]?",
+            "(?<a>01)(&a){3}",
+            "0{1,3}(1{,2}){2}",
+        ] {
+            let imex = IMEx::try_from(*imex_str)?;
+            let max = imex.max_items().expect("IMEx is bounded");
+            let iters = vec![iter::repeat(0); 5];
+            let given = IMExIter::sorted_by(iters, *imex_str, Ord::cmp)?
+                .take(max + 10)
+                .count();
+
+            assert!(imex.is_bounded());
+            assert!(given <= max, "{} gave {} items", imex_str, given);
+        }
+        Ok(())
+    }
+
+    /// Replaces the parameters of a printed IMEx with counts.
+    fn bind_printed_params(printed: &str) -> String {
+        let mut bound = String::new();
+        let mut rest = printed;
+        while let Some(start) = rest.find("{$") {
+            let end = start
+                + rest[start..]
+                    .find('}')
+                    .expect("Expected param to be closed");
+            bound.push_str(&rest[..start]);
+            bound.push_str("{2}");
+            rest = &rest[end + 1..];
+        }
+        bound.push_str(rest);
+        bound
+    }
+
+    fn merged(pattern: &Pattern, lens: &[usize], seed: u64) -> Result<(Vec<char>, Vec<bool>)> {
+        let iters = || {
+            lens.iter()
+                .enumerate()
+                .map(|(i, &len)| std::iter::repeat((b'a' + i as u8) as char).take(len))
+                .collect::<Vec<_>>()
+        };
+        let lenient = IMExIter::sorted_by(iters(), pattern, Ord::cmp)?
+            .seeded(seed)
+            .collect::<Vec<char>>();
+        let strict = IMExIter::sorted_by(iters(), pattern, Ord::cmp)?
+            .seeded(seed)
+            .strict()
+            .map(|item| item.is_ok())
+            .collect::<Vec<bool>>();
+        Ok((lenient, strict))
+    }
+
+    #[test]
+    fn simplified_imex_merges_like_original() -> Result<()> {
+        let mut numbers = Numbers::new(23);
+        for _ in 0..2000 {
+            let printed = bind_printed_params(&random::imex(&mut numbers, 3).to_string());
+            let imex = IMEx::try_from(printed.as_str())?;
+            let simplified = imex.simplify();
+
+            // The iterators aren't named, so names are resolved to indices for merging.
+            let mut names = imex.names();
+            names.sort_unstable();
+            names.dedup();
+            let names = names.into_iter().map(String::from).collect::<Vec<String>>();
+            let original = Pattern::from(imex.resolve_names(&names)?);
+            let simplified = Pattern::from(simplified.resolve_names(&names)?);
+            for _ in 0..4 {
+                let lens = (0..10).map(|_| numbers.below(4)).collect::<Vec<usize>>();
+                let seed = numbers.next_u64();
+
+                assert_eq!(
+                    merged(&simplified, &lens, seed)?,
+                    merged(&original, &lens, seed)?,
+                    "{} simplified to {} with {:?}",
+                    printed,
+                    simplified.imex(),
+                    lens
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,11 +0,0 @@
-//! This module contains objects for representing an IMEx.
-mod imex;
-mod imex_val;
-mod quantified_imex_val;
-mod quantifier;
-mod utils;
-
-pub use self::{
-    imex::IMEx, imex_val::IMExVal, quantified_imex_val::QuantifiedIMExVal, quantifier::Quantifier,
-};
-use utils::{IMExIterCounter, ParserCombinator};
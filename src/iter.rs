@@ -1,27 +1,301 @@
-use crate::expression::IMEx;
-use std::convert::TryFrom;
-use std::io::Result;
+use crate::{engine::Cursor, IntoPattern, Pattern, Rng};
+use std::cmp::Ordering;
+use std::io::{
+    Error,
+    ErrorKind::{InvalidData, InvalidInput},
+    Result,
+};
+use std::iter::Peekable;
 
-/// Similar to the standard Iterator, except its iteration function requires an extra argument.
+/// The iterators being merged by an [`IMExIter`](./struct.IMExIter.html). They are wrapped in
+/// Peekables, so they can be checked for remaining items without consuming them.
 ///
-/// Used on many of the objects in the expression module.
+/// Iterators are referred to by index, where negative indices count backwards from the last
+/// iterator.
+///
+/// Sources also holds the random number generator used for weighted random choices and the
+/// comparator used for sorted merges, so that they are shared by the whole IMEx.
+pub struct Sources<T>
+where
+    T: Iterator,
+{
+    iters: Vec<Peekable<T>>,
+    rng: Rng,
+    compare: Option<Comparator<T::Item>>,
+}
+
+/// A function that orders the items of the iterators being merged, for sorted merges.
+type Comparator<I> = Box<dyn Fn(&I, &I) -> Ordering>;
+
+impl<T> Sources<T>
+where
+    T: Iterator,
+{
+    /// Resolves a possibly negative index against the number of iterators. Returns None if the
+    /// index is out of range.
+    fn resolve_index(&self, index: isize) -> Option<usize> {
+        let index = if index < 0 {
+            self.iters.len() as isize + index
+        } else {
+            index
+        };
+        if index >= 0 && (index as usize) < self.iters.len() {
+            Some(index as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of iterators.
+    pub fn len(&self) -> usize {
+        self.iters.len()
+    }
+
+    /// Takes the next item from the iterator at `index`. Returns None if the index is out of
+    /// range or the iterator is exhausted.
+    pub fn next(&mut self, index: isize) -> Option<T::Item> {
+        let index = self.resolve_index(index)?;
+        self.iters[index].next()
+    }
+
+    /// Returns true if the iterator at `index` has no more items, without consuming any. An
+    /// out-of-range index is always exhausted.
+    pub fn is_exhausted(&mut self, index: isize) -> bool {
+        match self.resolve_index(index) {
+            Some(index) => self.iters[index].peek().is_none(),
+            None => true,
+        }
+    }
+
+    /// Reseeds the random number generator, so the same weighted random choices are made each
+    /// time the same seed is used.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Returns a random number that is less than `bound`, or 0 if `bound` is 0.
+    pub fn random_below(&mut self, bound: u64) -> u64 {
+        self.rng.below(bound)
+    }
+
+    /// Sets the comparator used to find the smallest item for sorted merges.
+    pub fn order_by(&mut self, compare: Comparator<T::Item>) {
+        self.compare = Some(compare);
+    }
+
+    /// Returns true if a comparator was set for sorted merges.
+    pub fn is_ordered(&self) -> bool {
+        self.compare.is_some()
+    }
+
+    /// Takes the smallest next item of the iterators at `indices`, using the comparator. Ties
+    /// are broken by the order of `indices`, and without a comparator every item is a tie.
+    /// Returns None if all of the iterators are exhausted.
+    pub fn next_smallest(&mut self, indices: &[isize]) -> Option<T::Item> {
+        let mut smallest: Option<usize> = None;
+        for &index in indices {
+            let position = match self.resolve_index(index) {
+                Some(position) => position,
+                None => continue,
+            };
+            if self.iters[position].peek().is_none() {
+                continue;
+            }
+            smallest = match (smallest, &self.compare) {
+                (Some(current), Some(compare)) if current != position => {
+                    let (head, current_head) = peek_pair(&mut self.iters, position, current);
+                    if compare(head, current_head) == Ordering::Less {
+                        Some(position)
+                    } else {
+                        Some(current)
+                    }
+                }
+                (Some(current), _) => Some(current),
+                (None, _) => Some(position),
+            };
+        }
+        self.iters[smallest?].next()
+    }
+
+    /// Takes the next item of the first iterator at `indices` that isn't exhausted, or of every
+    /// iterator if `indices` is None. The iterators are tried starting from `rotation`, which is
+    /// moved past the iterator that gave an item.
+    pub fn next_in_class(
+        &mut self,
+        rotation: &mut usize,
+        indices: Option<&[isize]>,
+    ) -> Option<T::Item> {
+        let len = indices.map_or(self.len(), <[isize]>::len);
+        for offset in 0..len {
+            let position = (*rotation + offset) % len;
+            let index = indices.map_or(position as isize, |indices| indices[position]);
+            if let Some(item) = self.next(index) {
+                *rotation = position + 1;
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+/// Peeks at the next items of two different iterators at once. Both iterators must have items.
+fn peek_pair<T: Iterator>(
+    iters: &mut [Peekable<T>],
+    first: usize,
+    second: usize,
+) -> (&T::Item, &T::Item) {
+    let (first, second) = if first < second {
+        let (left, right) = iters.split_at_mut(second);
+        (&mut left[first], &mut right[0])
+    } else {
+        let (left, right) = iters.split_at_mut(first);
+        (&mut right[0], &mut left[second])
+    };
+    match (first.peek(), second.peek()) {
+        (Some(first), Some(second)) => (first, second),
+        _ => unreachable!("peek_pair is only used on iterators with items"),
+    }
+}
+
+impl<T> From<Vec<T>> for Sources<T>
+where
+    T: Iterator,
+{
+    fn from(iters: Vec<T>) -> Sources<T> {
+        Sources {
+            iters: iters.into_iter().map(Iterator::peekable).collect(),
+            rng: Rng::from_entropy(),
+            compare: None,
+        }
+    }
+}
+
+/// The result of a single step of an [`IMExIterator`](./trait.IMExIterator.html).
+#[derive(PartialEq, Debug)]
+pub enum Step<I> {
+    /// An item was taken from the iters, and is part of the merge.
+    Item(I),
+    /// An item was taken from the iters, but was discarded.
+    Discarded,
+    /// No item was taken from the iters.
+    Done,
+}
+
+impl<I> From<Option<I>> for Step<I> {
+    fn from(item: Option<I>) -> Step<I> {
+        match item {
+            Some(item) => Step::Item(item),
+            None => Step::Done,
+        }
+    }
+}
+
+/// Similar to the standard Iterator, except its iteration function requires extra arguments.
+///
+/// Used by the compiled [`Program`](./struct.Program.html) of a Pattern, which is never changed
+/// by iterating it. Instead, the progress of each iteration is kept in a separate State, so a
+/// Pattern can be shared by any number of merges.
 pub trait IMExIterator {
-    /// Defines how the IMExIterator iterates and returns a result using the iters that an IMEx is
-    /// supposed to merge.
-    fn iterate<T, I>(&mut self, iters: &mut Vec<T>) -> Option<I>
+    /// The progress of a single iteration over this IMExIterator.
+    type State;
+
+    /// Returns the State of an iteration that hasn't taken anything yet.
+    fn start(&self) -> Self::State;
+
+    /// Defines how the IMExIterator takes a single item from the iters that an IMEx is supposed
+    /// to merge, and whether that item is part of the merge.
+    fn step<T, I>(&self, state: &mut Self::State, iters: &mut Sources<T>) -> Step<I>
     where
         T: Iterator<Item = I>;
+
+    /// Iterates the IMExIterator using the iters that an IMEx is supposed to merge, stepping past
+    /// discarded items until an item that is part of the merge is found.
+    fn iterate<T, I>(&self, state: &mut Self::State, iters: &mut Sources<T>) -> Option<I>
+    where
+        T: Iterator<Item = I>,
+    {
+        loop {
+            match self.step(state, iters) {
+                Step::Item(item) => return Some(item),
+                Step::Discarded => continue,
+                Step::Done => return None,
+            }
+        }
+    }
+
+    /// Whether a quantifier within this IMExIterator has finished without meeting its minimum
+    /// number of repetitions. Used to enforce quantifier minimums when merging strictly.
+    fn unmet_minimum(&self, _state: &Self::State) -> bool {
+        false
+    }
+}
+
+/// Checks that the IMEx of `pattern` refers to every one of `count` iterators, and to no others.
+/// Errors show the IMEx as it was written if the Pattern was parsed, and as it's printed if not.
+fn check_arity(pattern: &Pattern, count: usize) -> Result<()> {
+    let resolve = |index: isize| match index {
+        index if index < 0 => count.checked_sub(index.unsigned_abs()),
+        index => Some(index as usize).filter(|&index| index < count),
+    };
+    let imex = pattern.imex();
+    let (written, missing) = match pattern.source() {
+        Some(source) => (
+            source.imex.clone(),
+            source.indices.iter().find_map(|(indices, span)| {
+                let mut indices = indices.clone();
+                indices
+                    .find(|&index| resolve(index).is_none())
+                    .map(|index| (index, span.start))
+            }),
+        ),
+        None => (
+            imex.to_string(),
+            imex.index_positions()
+                .into_iter()
+                .find(|&(index, _)| resolve(index).is_none()),
+        ),
+    };
+    if let Some((index, position)) = missing {
+        return Err(Error::new(
+            InvalidInput,
+            format!(
+                "IMEx \"{}\" refers to iterator {} at position {}, but {} iterators were provided",
+                written, index, position, count
+            ),
+        ));
+    }
+    if !imex.refers_to_every_iterator() {
+        let referenced = imex
+            .referenced_indices()
+            .into_iter()
+            .filter_map(resolve)
+            .collect::<Vec<usize>>();
+        if let Some(unreferenced) = (0..count).find(|index| !referenced.contains(index)) {
+            return Err(Error::new(
+                InvalidInput,
+                format!(
+                    "Iterator {} was provided, but IMEx \"{}\" never refers to it",
+                    unreferenced, written
+                ),
+            ));
+        }
+    }
+    Ok(())
 }
 
 /// An iterator that lazily merges other iterators using an
 /// [`IMEx`](./expression/imex/struct.IMEx.html). The result of using the merge functions defined
 /// on the [`IMExMerges`](./trait.IMExMerges.html) trait.
+///
+/// The IMEx is kept in a [`Pattern`](./struct.Pattern.html), which isn't changed by merging, so
+/// the same Pattern can be used by many IMExIters at once.
 pub struct IMExIter<T, I>
 where
     T: Iterator<Item = I>,
 {
-    iters: Vec<T>,
-    imex: IMEx,
+    iters: Sources<T>,
+    pattern: Pattern,
+    cursor: Cursor,
 }
 
 impl<T, I> IMExIter<T, I>
@@ -29,10 +303,13 @@ where
     T: Iterator<Item = I>,
 {
     /// Constructs and [`IMExIter`](./struct.IMExIter.html) from a vector of iterators and an IMEx
-    /// string.
+    /// string, or a [`Pattern`](./struct.Pattern.html) that was already parsed.
     ///
     /// # Error
-    /// Results in an error if the provided IMEx is invalid.
+    /// Results in an error if the provided IMEx is invalid, if it has parameters, if it refers to
+    /// iterators by name, which only [`IMExIter::named`](#method.named) allows, or if it has
+    /// sorted merges, which need the comparator that [`IMExIter::sorted_by`](#method.sorted_by)
+    /// takes.
     ///
     /// # Example
     /// ```
@@ -45,12 +322,271 @@ where
     ///
     /// assert_eq!(merged, "12a34bcde");
     /// ```
-    pub fn new(iters: Vec<T>, imex: &str) -> Result<Self> {
+    pub fn new<X: IntoPattern>(iters: Vec<T>, imex: X) -> Result<Self> {
+        IMExIter::with_params(iters, imex, Vec::<(&str, usize)>::new())
+    }
+
+    /// Constructs an [`IMExIter`](./struct.IMExIter.html) like
+    /// [`IMExIter::new`](#method.new), but checks that the IMEx refers to exactly the iterators
+    /// provided. [`IMExIter::new`](#method.new) skips indices that are out of range, and
+    /// iterators that are never referred to are never merged.
+    ///
+    /// # Error
+    /// Results in an error if the provided IMEx is invalid, if it has parameters, if it refers to
+    /// iterators by name, or if it has sorted merges. Also results in an error naming the index
+    /// and the byte position where it's written in the IMEx string, if the IMEx refers to an
+    /// iterator that wasn't provided, or naming the iterator if one that was provided is never
+    /// referred to. For a Pattern built from an [`IMEx`](./expression/struct.IMEx.html) rather
+    /// than parsed, the position is in the IMEx as it's printed.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExIter;
+    ///
+    /// let error = IMExIter::exact(vec!["000".chars(), "111".chars()], "0120")
+    ///     .err()
+    ///     .expect("IMEx refers to a missing iterator");
+    ///
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "IMEx \"0120\" refers to iterator 2 at position 2, but 2 iterators were provided"
+    /// );
+    /// ```
+    pub fn exact<X: IntoPattern>(iters: Vec<T>, imex: X) -> Result<Self> {
+        let pattern = imex.into_pattern()?;
+        check_arity(&pattern, iters.len())?;
+        IMExIter::new(iters, pattern)
+    }
+
+    /// Constructs an [`IMExIter`](./struct.IMExIter.html) like [`IMExIter::new`](#method.new),
+    /// for an IMEx with sorted merges, like `<012>`, which give the smallest next item of the
+    /// iterators they list, as ordered by `compare`. Ties go to the iterator listed first.
+    ///
+    /// # Error
+    /// Results in an error if the provided IMEx is invalid, if it has parameters, which can be
+    /// bound first with [`Pattern::with_params`](./struct.Pattern.html#method.with_params), or if
+    /// it refers to iterators by name.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExIter;
+    ///
+    /// let iters = vec!["adg".chars(), "beh".chars(), "cf".chars()];
+    /// let merged = IMExIter::sorted_by(iters, "<012>*", |a, b| a.cmp(b))
+    ///     .expect("Invalid IMEx")
+    ///     .collect::<String>();
+    ///
+    /// assert_eq!(merged, "abcdefgh");
+    /// ```
+    pub fn sorted_by<X, F>(iters: Vec<T>, imex: X, compare: F) -> Result<Self>
+    where
+        X: IntoPattern,
+        F: Fn(&I, &I) -> Ordering + 'static,
+    {
+        let mut iters = Sources::from(iters);
+        iters.order_by(Box::new(compare));
+        IMExIter::from_pattern(iters, imex.into_pattern()?)
+    }
+
+    /// Constructs an [`IMExIter`](./struct.IMExIter.html) like
+    /// [`IMExIter::sorted_by`](#method.sorted_by), with sorted merges that compare the keys that
+    /// `key_fn` gives for each item.
+    ///
+    /// # Error
+    /// Results in an error if the provided IMEx is invalid, if it has parameters, or if it refers
+    /// to iterators by name.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExIter;
+    ///
+    /// let iters = vec![vec![3, 1].into_iter(), vec![4, 2, 0].into_iter()];
+    /// let merged = IMExIter::sorted_by_key(iters, "<01>*", |n: &i32| -n)
+    ///     .expect("Invalid IMEx")
+    ///     .collect::<Vec<i32>>();
+    ///
+    /// assert_eq!(merged, vec![4, 3, 2, 1, 0]);
+    /// ```
+    pub fn sorted_by_key<X, K, F>(iters: Vec<T>, imex: X, key_fn: F) -> Result<Self>
+    where
+        X: IntoPattern,
+        K: Ord,
+        F: Fn(&I) -> K + 'static,
+    {
+        IMExIter::sorted_by(iters, imex, move |a, b| key_fn(a).cmp(&key_fn(b)))
+    }
+
+    /// Constructs an IMExIter from Sources and a Pattern, checking that the Pattern has no
+    /// parameters left to bind, no names that weren't resolved by
+    /// [`IMExIter::named`](#method.named), and no sorted merges unless the Sources have a
+    /// comparator.
+    fn from_pattern(iters: Sources<T>, pattern: Pattern) -> Result<Self> {
+        if let Some(param) = pattern.imex().first_param() {
+            return Err(Error::new(
+                InvalidInput,
+                format!("IMEx parameter \"{}\" isn't bound", param),
+            ));
+        }
+        if let Some(name) = pattern.imex().names().first() {
+            return Err(Error::new(
+                InvalidInput,
+                format!(
+                    "IMEx refers to iterator name \"{}\", but the iterators aren't named",
+                    name
+                ),
+            ));
+        }
+        if pattern.imex().has_sorted() && !iters.is_ordered() {
+            return Err(Error::new(
+                InvalidInput,
+                "IMEx has a sorted merge, but no comparator was provided to order its items",
+            ));
+        }
         Ok(IMExIter::<T, I> {
             iters,
-            imex: IMEx::try_from(imex)?,
+            cursor: pattern.program().start(),
+            pattern,
         })
     }
+
+    /// Constructs an [`IMExIter`](./struct.IMExIter.html) from a vector of iterators and an IMEx
+    /// string or [`Pattern`](./struct.Pattern.html) with parameters, like `{$name}`, which are
+    /// bound to counts by `params`. The parameters can be provided by anything that iterates over
+    /// `(name, count)` pairs, like a `HashMap` or a vector of tuples.
+    ///
+    /// # Error
+    /// Results in an error if the provided IMEx is invalid, if it has a parameter that wasn't
+    /// provided, if the same parameter was provided twice, if it refers to iterators by name, or if
+    /// it has sorted merges. An IMEx with both parameters and sorted merges can be bound with
+    /// [`Pattern::with_params`](./struct.Pattern.html#method.with_params) and then given to
+    /// [`IMExIter::sorted_by`](#method.sorted_by).
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExIter;
+    ///
+    /// let merged = IMExIter::with_params(
+    ///     vec!["1234".chars(), "abcde".chars()],
+    ///     "(0{$burst}1)*",
+    ///     vec![("burst", 2)],
+    /// )
+    /// .expect("Invalid IMEx")
+    /// .collect::<String>();
+    ///
+    /// assert_eq!(merged, "12a34bcde");
+    /// ```
+    pub fn with_params<X, P, S>(iters: Vec<T>, imex: X, params: P) -> Result<Self>
+    where
+        X: IntoPattern,
+        P: IntoIterator<Item = (S, usize)>,
+        S: AsRef<str>,
+    {
+        let pattern = imex.into_pattern()?.with_params(params)?;
+        IMExIter::from_pattern(Sources::from(iters), pattern)
+    }
+
+    /// Constructs an [`IMExIter`](./struct.IMExIter.html) from named iterators and an IMEx
+    /// string or [`Pattern`](./struct.Pattern.html) that refers to them by name. The iterators
+    /// can be provided by anything that iterates over `(name, iterator)` pairs, like a `HashMap`
+    /// or a vector of tuples.
+    ///
+    /// Names that are a single letter can be used directly in the IMEx, while longer names need
+    /// to be bracketed, like `[name]`.
+    ///
+    /// # Error
+    /// Results in an error if the provided IMEx is invalid, if it refers to a name that wasn't
+    /// provided, if the same name was provided twice, if it has parameters, or if it has sorted
+    /// merges.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExIter;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut iters = HashMap::new();
+    /// iters.insert("a", "1234".chars());
+    /// iters.insert("bee", "abcde".chars());
+    ///
+    /// let merged = IMExIter::named(iters, "(a{2}[bee])*")
+    ///     .expect("Invalid IMEx")
+    ///     .collect::<String>();
+    ///
+    /// assert_eq!(merged, "12a34bcde");
+    /// ```
+    pub fn named<X, N, S>(iters: N, imex: X) -> Result<Self>
+    where
+        X: IntoPattern,
+        N: IntoIterator<Item = (S, T)>,
+        S: AsRef<str>,
+    {
+        let (names, iters): (Vec<S>, Vec<T>) = iters.into_iter().unzip();
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].iter().any(|n| n.as_ref() == name.as_ref()) {
+                return Err(Error::new(
+                    InvalidInput,
+                    format!("Iterator name \"{}\" was provided twice", name.as_ref()),
+                ));
+            }
+        }
+        let imex = imex.into_pattern()?.imex().clone().resolve_names(&names)?;
+        IMExIter::from_pattern(Sources::from(iters), Pattern::from(imex))
+    }
+}
+
+impl<T, I> IMExIter<T, I>
+where
+    T: Iterator<Item = I>,
+{
+    /// Converts this [`IMExIter`](./struct.IMExIter.html) into a
+    /// [`StrictIMExIter`](./struct.StrictIMExIter.html), which treats the minimums of the IMEx's
+    /// quantifiers as requirements rather than limits.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExIter;
+    ///
+    /// let mut strict_iter = IMExIter::new(vec!["1234".chars(), "ab".chars()], "(0{2}1{1,})*")
+    ///     .expect("Invalid IMEx")
+    ///     .strict();
+    ///
+    /// assert_eq!(strict_iter.next().unwrap().unwrap(), '1');
+    /// assert_eq!(strict_iter.next().unwrap().unwrap(), '2');
+    /// assert_eq!(strict_iter.next().unwrap().unwrap(), 'a');
+    /// assert_eq!(strict_iter.next().unwrap().unwrap(), 'b');
+    /// assert_eq!(strict_iter.next().unwrap().unwrap(), '3');
+    /// assert_eq!(strict_iter.next().unwrap().unwrap(), '4');
+    /// assert!(strict_iter.next().unwrap().is_err());
+    /// assert!(strict_iter.next().is_none());
+    /// ```
+    pub fn strict(mut self) -> StrictIMExIter<T, I> {
+        self.cursor.set_strict();
+        StrictIMExIter {
+            imex_iter: self,
+            failed: false,
+        }
+    }
+
+    /// Seeds the random number generator used by the weighted random choices of the IMEx, like
+    /// `(0%70|1%30)`, so that merging the same iterators with the same seed always gives the
+    /// same result. Without a seed, the choices are different each time.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExIter;
+    ///
+    /// let merge = |seed| {
+    ///     IMExIter::new(vec!["1234".chars(), "abcd".chars()], "(0%70|1%30)*")
+    ///         .expect("Invalid IMEx")
+    ///         .seeded(seed)
+    ///         .collect::<String>()
+    /// };
+    ///
+    /// assert_eq!(merge(42), merge(42));
+    /// ```
+    pub fn seeded(mut self, seed: u64) -> Self {
+        self.iters.seed(seed);
+        self
+    }
 }
 
 impl<T, I> Iterator for IMExIter<T, I>
@@ -60,13 +596,58 @@ where
     type Item = I;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.imex.iterate(&mut self.iters)
+        self.pattern
+            .program()
+            .iterate(&mut self.cursor, &mut self.iters)
+    }
+}
+
+/// An [`IMExIter`](./struct.IMExIter.html) that requires every quantifier of its IMEx to repeat at
+/// least its minimum number of times. The result of using
+/// [`IMExIter::strict`](./struct.IMExIter.html#method.strict).
+///
+/// The minimum of `{x}` is `x`, the minimum of `{m,n}` is `m`, and the minimum of `*` is 0. A digit
+/// or group without a quantifier needs to be repeated once. Repetitions that end because the
+/// relevant iterator\(s\) are exhausted before giving any items are not counted.
+///
+/// Items are wrapped in an `Ok` until a minimum isn't met, at which point an `Err` is given and the
+/// merge ends. No more items are taken from the iterators once a minimum isn't met.
+pub struct StrictIMExIter<T, I>
+where
+    T: Iterator<Item = I>,
+{
+    imex_iter: IMExIter<T, I>,
+    failed: bool,
+}
+
+impl<T, I> Iterator for StrictIMExIter<T, I>
+where
+    T: Iterator<Item = I>,
+{
+    type Item = Result<I>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.imex_iter.next();
+        let imex_iter = &self.imex_iter;
+        if imex_iter.pattern.program().unmet_minimum(&imex_iter.cursor) {
+            self.failed = true;
+            return Some(Err(Error::new(
+                InvalidData,
+                "Iterators were exhausted before an IMEx quantifier's minimum was met",
+            )));
+        }
+        next.map(Ok)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{expression::IMEx, IMExMerges};
+    use std::convert::TryFrom;
 
     #[test]
     fn non_repeating_imex_might_not_complete() -> Result<()> {
@@ -108,6 +689,527 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn exact_imex_merges_like_new() -> Result<()> {
+        let iters = vec!["000".chars(), "111".chars(), "222".chars()];
+        let i = IMExIter::exact(iters, "(0[-1]1)*")?;
+
+        assert_eq!(i.collect::<String>(), "021021021");
+
+        let iters = vec!["000".chars(), "111".chars()];
+        assert_eq!(IMExIter::exact(iters, "0.*")?.collect::<String>(), "001011");
+
+        Ok(())
+    }
+
+    #[test]
+    fn exact_imex_with_missing_iterator_fails() {
+        let error = IMExIter::exact(vec!["000".chars(), "111".chars()], "(01)*(0|[-3])")
+            .err()
+            .expect("Expected missing iterator to fail");
+
+        assert_eq!(
+            error.to_string(),
+            "IMEx \"(01)*(0|[-3])\" refers to iterator -3 at position 8, but 2 iterators were \
+             provided"
+        );
+        assert!(IMExIter::exact(vec!["0".chars()], "0<01>").is_err());
+    }
+
+    #[test]
+    fn exact_imex_with_missing_iterator_points_into_imex_string() -> Result<()> {
+        let error = |imex: &str| {
+            IMExIter::exact(vec!["0".chars()], imex)
+                .err()
+                .expect("Expected missing iterator to fail")
+                .to_string()
+        };
+
+        assert_eq!(
+            error("0{1}0{1}5"),
+            "IMEx \"0{1}0{1}5\" refers to iterator 5 at position 8, but 1 iterators were provided"
+        );
+        assert_eq!(
+            error("(?x) 0  # first\n [0-3]"),
+            "IMEx \"(?x) 0  # first\n [0-3]\" refers to iterator 1 at position 18, but 1 \
+             iterators were provided"
+        );
+
+        let built = Pattern::from(IMEx::try_from("0{1}0{1}5")?);
+        let error = IMExIter::exact(vec!["0".chars()], built)
+            .err()
+            .expect("Expected missing iterator to fail");
+        assert_eq!(
+            error.to_string(),
+            "IMEx \"005\" refers to iterator 5 at position 2, but 1 iterators were provided"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn exact_imex_with_unreferenced_iterator_fails() {
+        let iters = vec!["000".chars(), "111".chars(), "222".chars()];
+        let error = IMExIter::exact(iters, "(0~2)*")
+            .err()
+            .expect("Expected unreferenced iterator to fail");
+
+        assert_eq!(
+            error.to_string(),
+            "Iterator 1 was provided, but IMEx \"(0~2)*\" never refers to it"
+        );
+    }
+
+    #[test]
+    fn bracketed_imex_reaches_past_nine() -> Result<()> {
+        let iters = "abcdefghijkl"
+            .chars()
+            .map(|c| {
+                c.to_string()
+                    .repeat(2)
+                    .chars()
+                    .collect::<Vec<char>>()
+                    .into_iter()
+            })
+            .collect::<Vec<_>>();
+        let i = IMExIter::new(iters, "([11][10]9)*")?;
+
+        assert_eq!(i.collect::<String>(), "lkjlkj");
+
+        Ok(())
+    }
+
+    #[test]
+    fn negative_imex_counts_from_last() -> Result<()> {
+        let iters = vec!["000".chars(), "111".chars(), "222".chars()];
+        let i = IMExIter::new(iters, "[-1]0[-2]{2}[-4]")?;
+
+        assert_eq!(i.collect::<String>(), "2011");
+
+        Ok(())
+    }
+
+    #[test]
+    fn named_imex_merges_named_iters() -> Result<()> {
+        let iters = vec![
+            ("b", "111".chars()),
+            ("a", "000".chars()),
+            ("cc", "222".chars()),
+        ];
+        let i = IMExIter::named(iters, "(a{2}[cc]b)*")?;
+
+        assert_eq!(i.collect::<String>(), "002102121");
+
+        Ok(())
+    }
+
+    #[test]
+    fn named_imex_with_unknown_name_fails() {
+        let iters = vec![("a", "000".chars()), ("b", "111".chars())];
+        assert!(IMExIter::named(iters, "(ab)*c").is_err());
+    }
+
+    #[test]
+    fn names_fail_unless_iterators_are_named() {
+        let iters = || vec!["ab".chars(), "cd".chars()];
+        assert!(IMExIter::new(iters(), "(0x)*").is_err());
+        assert!(IMExIter::exact(iters(), "01$x").is_err());
+        assert!(IMExIter::with_params(iters(), "<0[one]>{$n}", vec![("n", 2)]).is_err());
+        assert!("ab"
+            .chars()
+            .imex_merge("cd".chars(), "(0(?!x:1))*")
+            .is_err());
+        assert!(IMExIter::boxed(("ab".chars(), vec!['c']), "0~a").is_err());
+    }
+
+    #[test]
+    fn named_imex_with_duplicate_name_fails() {
+        let iters = vec![("a", "000".chars()), ("a", "111".chars())];
+        assert!(IMExIter::named(iters, "(a)*").is_err());
+    }
+
+    #[test]
+    fn param_imex_merges_with_bound_counts() -> Result<()> {
+        let iters = vec!["000000".chars(), "111".chars()];
+        let i = IMExIter::with_params(iters, "(0{$burst}1)*", vec![("burst", 2)])?;
+
+        assert_eq!(i.collect::<String>(), "001001001");
+
+        Ok(())
+    }
+
+    #[test]
+    fn param_imex_with_unbound_param_fails() {
+        let iters = vec!["000".chars(), "111".chars()];
+        assert!(IMExIter::new(iters.clone(), "(0{$burst}1)*").is_err());
+        assert!(IMExIter::with_params(iters, "(0{$burst}1)*", vec![("bust", 2)]).is_err());
+    }
+
+    #[test]
+    fn param_imex_with_duplicate_param_fails() {
+        let iters = vec!["000".chars(), "111".chars()];
+        let params = vec![("burst", 2), ("burst", 3)];
+        assert!(IMExIter::with_params(iters, "(0{$burst}1)*", params).is_err());
+    }
+
+    #[test]
+    fn weighted_imex_is_deterministic_with_seed() -> Result<()> {
+        let merge = |seed| -> Result<String> {
+            let iters = vec![
+                std::iter::repeat('0').take(100),
+                std::iter::repeat('1').take(100),
+            ];
+            Ok(IMExIter::new(iters, "(0%70|1%30)*")?
+                .seeded(seed)
+                .collect::<String>())
+        };
+
+        assert_eq!(merge(1)?, merge(1)?);
+        assert_ne!(merge(1)?, merge(2)?);
+        assert_eq!(merge(1)?.len(), 200);
+
+        Ok(())
+    }
+
+    #[test]
+    fn weighted_imex_follows_weights() -> Result<()> {
+        let iters = vec![
+            std::iter::repeat('0').take(10000),
+            std::iter::repeat('1').take(10000),
+            std::iter::repeat('2').take(10000),
+        ];
+        let merged = IMExIter::new(iters, "(0%60|1%30|2%10){10000}")?
+            .seeded(3)
+            .collect::<String>();
+        let count = |c| merged.chars().filter(|m| *m == c).count();
+
+        assert_eq!(merged.len(), 10000);
+        assert!(count('0') > 5800 && count('0') < 6200, "{}", count('0'));
+        assert!(count('1') > 2800 && count('1') < 3200, "{}", count('1'));
+        assert!(count('2') > 850 && count('2') < 1150, "{}", count('2'));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_imex_merges_sorted_iters() -> Result<()> {
+        let iters = vec!["aceg".chars(), "bdf".chars(), "h".chars()];
+        let i = IMExIter::sorted_by(iters, "<012>*", |a, b| a.cmp(b))?;
+
+        assert_eq!(i.collect::<String>(), "abcdefgh");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_imex_combines_with_pattern() -> Result<()> {
+        let iters = vec!["acf".chars(), "bde".chars(), "|||".chars()];
+        let i = IMExIter::sorted_by(iters, "(<01>{2}2)*", |a, b| a.cmp(b))?;
+
+        assert_eq!(i.collect::<String>(), "ab|cd|ef|");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_imex_orders_by_key() -> Result<()> {
+        let iters = vec!["Ac".chars(), "bD".chars()];
+        let i = IMExIter::sorted_by_key(iters, "<01>*", |c: &char| c.to_ascii_lowercase())?;
+
+        assert_eq!(i.collect::<String>(), "AbcD");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_imex_without_comparator_fails() {
+        let iters = || vec!["ad".chars(), "bc".chars()];
+        assert!(IMExIter::new(iters(), "<01>*").is_err());
+        assert!(IMExIter::new(iters(), "(0|~<01>)*").is_err());
+        assert!(IMExIter::new(iters(), "(01)*").is_ok());
+
+        let named = vec![("x", "ad".chars()), ("y", "bc".chars())];
+        assert!(IMExIter::named(named, "(?x:<xy>)*").is_err());
+    }
+
+    #[test]
+    fn range_imex_repeats_up_to_max() -> Result<()> {
+        let iters = vec!["00000000".chars(), "111111".chars()];
+        let i = IMExIter::new(iters, "(0{1,3}1{,2})*")?;
+
+        assert_eq!(i.collect::<String>(), "00011000110011");
+
+        Ok(())
+    }
+
+    #[test]
+    fn plus_and_question_mark_imex_repeat() -> Result<()> {
+        let iters = vec!["0000".chars(), "111".chars(), "22".chars()];
+        let i = IMExIter::new(iters, "(01?)+2?")?;
+
+        assert_eq!(i.collect::<String>(), "01010102");
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_imex_with_unmet_plus_fails() -> Result<()> {
+        let iters = vec!["00".chars(), "".chars()];
+        let mut i = IMExIter::new(iters, "1+0*")?.strict();
+
+        assert!(i.next().transpose().is_err());
+        assert!(i.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_imex_with_skipped_question_mark_succeeds() -> Result<()> {
+        let iters = vec!["00".chars(), "".chars()];
+        let i = IMExIter::new(iters, "1?0*")?.strict();
+
+        assert_eq!(i.collect::<Result<String>>()?, "00");
+
+        Ok(())
+    }
+
+    #[test]
+    fn alternation_imex_falls_back_on_exhaustion() -> Result<()> {
+        let iters = vec!["00".chars(), "1111".chars()];
+        let i = IMExIter::new(iters, "(0|1)*")?;
+
+        assert_eq!(i.collect::<String>(), "001111");
+
+        Ok(())
+    }
+
+    #[test]
+    fn alternation_imex_keeps_pattern_aligned() -> Result<()> {
+        let iters = vec!["00".chars(), "1111".chars(), "222222".chars()];
+        let i = IMExIter::new(iters, "((0|1)2)*")?;
+
+        assert_eq!(i.collect::<String>(), "020212121212");
+
+        Ok(())
+    }
+
+    #[test]
+    fn class_imex_rotates_through_unexhausted_iters() -> Result<()> {
+        let iters = vec!["00".chars(), "1111".chars(), "2".chars(), "333".chars()];
+        let i = IMExIter::new(iters, "[0-2]*3")?;
+
+        assert_eq!(i.collect::<String>(), "01201113");
+
+        Ok(())
+    }
+
+    #[test]
+    fn class_imex_rotation_continues_across_groups() -> Result<()> {
+        let iters = vec!["000".chars(), "111".chars(), "xxxxxx".chars()];
+        let i = IMExIter::new(iters, "([0,1]2)*")?;
+
+        assert_eq!(i.collect::<String>(), "0x1x0x1x0x1x");
+
+        Ok(())
+    }
+
+    #[test]
+    fn wildcard_imex_interleaves_any_number_of_iters() -> Result<()> {
+        let iters = vec![
+            "0".chars(),
+            "111".chars(),
+            "22".chars(),
+            "".chars(),
+            "4".chars(),
+        ];
+        let i = IMExIter::new(iters, "(.)*")?;
+
+        assert_eq!(i.collect::<String>(), "0124121");
+
+        Ok(())
+    }
+
+    #[test]
+    fn wildcard_imex_takes_next_in_rotation() -> Result<()> {
+        let iters = vec!["000000".chars(), "11".chars(), "22".chars()];
+        let i = IMExIter::new(iters, "(0{2}.)*")?;
+
+        assert_eq!(i.collect::<String>(), "0000010212");
+
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_imex_runs_while_iter_has_items() -> Result<()> {
+        let iters = vec!["000".chars(), "111111".chars()];
+        let i = IMExIter::new(iters, "(?0:01)*1*")?;
+
+        assert_eq!(i.collect::<String>(), "010101111");
+
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_imex_doesnt_consume_checked_iter() -> Result<()> {
+        let iters = vec!["0".chars(), "11".chars()];
+        let i = IMExIter::new(iters, "(?0:1)0(?0:1)(?!0:1)")?;
+
+        assert_eq!(i.collect::<String>(), "101");
+
+        Ok(())
+    }
+
+    #[test]
+    fn called_group_repeats_named_group() -> Result<()> {
+        let iters = vec!["000000000".chars(), "111".chars(), "22".chars()];
+        let i = IMExIter::new(iters, "(?<burst>0{3}1)(2(&burst))*")?;
+
+        assert_eq!(i.collect::<String>(), "00012000120001");
+
+        Ok(())
+    }
+
+    #[test]
+    fn anchored_imex_waits_for_exhaustion() -> Result<()> {
+        let iters = vec!["000".chars(), "111".chars()];
+        let i = IMExIter::new(iters, "(0{2}$01)*")?;
+
+        assert_eq!(i.collect::<String>(), "000111");
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_anchor_skips_rest_of_imex() -> Result<()> {
+        let iters = vec!["000".chars(), "111".chars()];
+        let i = IMExIter::new(iters, "0$01*")?;
+
+        assert_eq!(i.collect::<String>(), "0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_anchor_falls_back_to_next_alternative() -> Result<()> {
+        let iters = vec!["00".chars(), "111".chars(), "222".chars()];
+        let i = IMExIter::new(iters, "(0($01|2))*")?;
+
+        assert_eq!(i.collect::<String>(), "02011122");
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_imex_with_passed_anchor_succeeds() -> Result<()> {
+        let iters = vec!["00".chars(), "1".chars()];
+        let i = IMExIter::new(iters, "0*$01")?.strict();
+
+        assert_eq!(i.collect::<Result<String>>()?, "001");
+
+        Ok(())
+    }
+
+    #[test]
+    fn discarding_imex_skips_items() -> Result<()> {
+        let iters = vec!["h0000".chars(), "1111".chars()];
+        let i = IMExIter::new(iters, "~0(0~1)*")?;
+
+        assert_eq!(i.collect::<String>(), "0000");
+
+        Ok(())
+    }
+
+    #[test]
+    fn discarding_imex_counts_towards_quantifiers() -> Result<()> {
+        let iters = vec!["abcdef".chars(), "111".chars()];
+        let i = IMExIter::new(iters, "(~0{2}1)*")?;
+
+        assert_eq!(i.collect::<String>(), "111");
+
+        Ok(())
+    }
+
+    #[test]
+    fn discarding_imex_repeats_until_exhausted() -> Result<()> {
+        let iters = vec!["abcdef".chars(), "111".chars()];
+        let i = IMExIter::new(iters, "(~0)*1*")?;
+
+        assert_eq!(i.collect::<String>(), "111");
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_imex_with_met_minimums_gives_all_items() -> Result<()> {
+        let iters = vec!["00000".chars(), "11111".chars()];
+        let i = IMExIter::new(iters, "(0{3}1{2,3})(01)*")?.strict();
+
+        assert_eq!(i.collect::<Result<String>>()?, "0001110101");
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_imex_with_unmet_minimum_fails() -> Result<()> {
+        let iters = vec!["000000".chars(), "1".chars()];
+        let i = IMExIter::new(iters, "(0{2}1)*")?.strict();
+        let merged = i.collect::<Vec<Result<char>>>();
+
+        assert_eq!(merged.len(), 6);
+        assert!(merged[..5].iter().all(|res| res.is_ok()));
+        assert!(merged[5].is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_imex_with_unmet_range_minimum_fails() -> Result<()> {
+        let iters = vec!["00".chars(), "1".chars()];
+        let mut i = IMExIter::new(iters, "0{3,5}1")?.strict();
+
+        assert_eq!(i.next().transpose()?, Some('0'));
+        assert_eq!(i.next().transpose()?, Some('0'));
+        assert!(i.next().transpose().is_err());
+        assert!(i.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_imex_with_unmet_minimum_leaves_next_item() -> Result<()> {
+        let mut zeros = "0".chars();
+        let mut ones = "1".chars();
+        let iters = vec![zeros.by_ref(), ones.by_ref()];
+        let mut i = IMExIter::new(iters, "0{3}1")?.strict();
+
+        assert_eq!(i.next().transpose()?, Some('0'));
+        assert!(i.next().transpose().is_err());
+        assert!(i.next().is_none());
+        drop(i);
+        assert_eq!(ones.next(), Some('1'));
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_imex_with_unfinished_last_repetition_succeeds() -> Result<()> {
+        let iters = vec!["00".chars(), "1".chars()];
+        let i = IMExIter::new(iters, "(0{2}1?)*")?.strict();
+
+        assert_eq!(i.collect::<Result<String>>()?, "001");
+
+        Ok(())
+    }
+
+    #[test]
+    fn non_strict_imex_ignores_unmet_minimum() -> Result<()> {
+        let iters = vec!["00".chars(), "1".chars()];
+        let i = IMExIter::new(iters, "0{3,5}1")?;
+
+        assert_eq!(i.collect::<String>(), "001");
+
+        Ok(())
+    }
+
     #[test]
     fn empty_imex_gives_empty_merge() -> Result<()> {
         let iters = vec!["000".chars(), "111".chars()];
@@ -137,4 +1239,49 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn pattern_is_reused_by_merges() -> Result<()> {
+        let pattern = Pattern::new("0{$n}1")?;
+
+        let first = IMExIter::with_params(vec!["aaa".chars(), "b".chars()], &pattern, [("n", 2)])?;
+        let second = IMExIter::with_params(vec!["ccc".chars(), "d".chars()], &pattern, [("n", 3)])?;
+
+        assert_eq!(first.collect::<String>(), "aab");
+        assert_eq!(second.collect::<String>(), "cccd");
+
+        Ok(())
+    }
+
+    #[test]
+    fn unbound_pattern_param_fails() -> Result<()> {
+        let pattern = Pattern::new("0{$n}")?;
+
+        assert!(IMExIter::new(vec!["aaa".chars()], &pattern).is_err());
+        assert!(IMExIter::named(vec![("a", "aaa".chars())], pattern).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_is_shared_between_threads() -> Result<()> {
+        let pattern = Pattern::new("(01)*")?;
+
+        let handles = (0..4)
+            .map(|n| {
+                let pattern = pattern.clone();
+                std::thread::spawn(move || {
+                    let left = n.to_string().repeat(3);
+                    IMExIter::new(vec![left.chars(), "xyz".chars()], pattern)
+                        .map(|i| i.collect::<String>())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for (n, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap()?, format!("{0}x{0}y{0}z", n));
+        }
+
+        Ok(())
+    }
 }
@@ -4,11 +4,54 @@
 //! # Writing an IMEx
 //! IMEx is based off of RegEx. A quick reference of IMEx syntax:
 //!  * Digits - indicates the index of the iterator to consume an item from
+//!  * `[x]` - indicates the index `x` of the iterator to consume an item from, for
+//!    indices past 9. Negative indices count backwards from the last iterator, so
+//!    `[-1]` refers to the last one.
+//!  * `[a,b,...]` and `[a-b]` - indicates a class of iterators to consume an item
+//!    from. The item is taken from the first iterator in the class that isn't
+//!    exhausted, and the starting point rotates each time, so `[0-3]*` fairly
+//!    rotates through the first four iterators until they are all exhausted
+//!  * `.` - indicates that an item should be consumed from the next iterator that
+//!    isn't exhausted, rotating through every iterator, so `(.)*` fairly interleaves
+//!    any number of iterators
+//!  * Letters and `[name]` - refers to an iterator by name, when the iterators being
+//!    merged are named
 //!  * `()` - defines a group
+//!  * `<...>` - gives the smallest next item of the listed iterators, so `<012>*`
+//!    merges three sorted iterators into one sorted iterator. Items are compared
+//!    with the comparator given to `IMExIter::sorted_by`
+//!  * `(?x:...)` - defines a group that is only used if iterator `x` isn't exhausted
+//!    when the group is reached. `(?!x:...)` is only used if iterator `x` is exhausted
+//!  * `(?<name>...)` - defines a named group, which is used in place like `(...)`
+//!    and can be reused anywhere in the IMEx with `(&name)`. Groups can call other
+//!    groups, but can't call themselves
+//!  * `$x` - checks that iterator `x` is exhausted, without consuming any items. If
+//!    it isn't, the rest of the group containing the anchor is skipped
+//!  * `~` - discards the items of the following digit/group instead of merging
+//!    them, so `~0{2}` skips two items of the first iterator
+//!  * `|` - separates alternatives within a group. The first alternative whose
+//!    iterator\(s\) aren't exhausted is used, so `(0|1)` takes an item from the first
+//!    iterator, or the second if the first is exhausted
+//!  * `%` - weights the alternatives of a group, like `(0%70|1%30)`, so that one
+//!    alternative is picked at random by weight. The random choices can be made
+//!    reproducible with `IMExIter::seeded`
 //!  * `*` - repeats the previous digit/group until the relevant iterator\(s\) are
 //!    exhausted
+//!  * `+` - repeats the previous digit/group until the relevant iterator\(s\) are
+//!    exhausted, like `*`, but requires at least one repetition when merging strictly
+//!  * `?` - repeats the previous digit/group once, if the relevant iterator\(s\)
+//!    aren't exhausted
 //!  * `{x}` - repeats the previous digit/group `x` times, or until the relevant
 //!    iterator\(s\) are exhausted.
+//!  * `{m,n}` - repeats the previous digit/group at most `n` times, or until the
+//!    relevant iterator\(s\) are exhausted. `m` is the minimum number of
+//!    repetitions, which is only enforced when merging strictly. Either bound can be
+//!    left out, as in `{m,}` or `{,n}`.
+//!  * `{$name}` - repeats the previous digit/group like `{x}`, where the count is a
+//!    parameter bound when the IMEx is used, as with `IMExIter::with_params`
+//!  * `(?x)` - when at the very start of an IMEx, turns on extended mode, which
+//!    ignores whitespace and `#` comments between tokens so an IMEx can span
+//!    several lines
 //!
 //! ## Example IMExes
 //!
@@ -45,11 +88,55 @@
 //!  * [`imex_merge_all`](./trait.IMExMerges.html#tymethod.imex_merge_all)
 //!  * [`rot_merge_all`](./trait.IMExMerges.html#method.rot_merge_all)
 //!  * [`alt_merge`](./trait.IMExMerges.html#method.alt_merge)
+//!
+//! Iterators of different types that give the same type of item can be merged by passing them
+//! as a tuple to [`imex_merge_mixed`](./trait.IMExMerges.html#method.imex_merge_mixed) or
+//! [`IMExIter::boxed`](./struct.IMExIter.html#method.boxed), which box them into
+//! [`BoxedIter`](./type.BoxedIter.html)s.
+//!
+//! Iterators can also be referred to by name, by constructing an
+//! [`IMExIter`](./struct.IMExIter.html) with [`IMExIter::named`](./struct.IMExIter.html#method.named).
+//!
+//! An IMEx that is used by many merges can be parsed once into a
+//! [`Pattern`](./struct.Pattern.html), which can be passed to any of the `IMExIter` constructors in
+//! place of the IMEx string, and shared between threads.
+//!
+//! IMExes can also be constructed from code, without a string, with the functions of the
+//! [`builder`](./expression/builder/index.html) module. The methods of the
+//! [`analysis`](./expression/analysis/index.html) module tell which iterators an IMEx refers to,
+//! and how many items it can give, before it's used to merge.
+//!
+//! With the `macros` feature enabled, the [`imex!`](./macro.imex.html) macro parses an IMEx when
+//! the program is compiled, so an invalid IMEx fails to compile instead of failing to merge.
 
+mod boxed;
+mod engine;
 pub mod expression;
 mod iter;
 mod merges;
+mod pattern;
+mod rng;
 
-pub use iter::IMExIter;
-use iter::IMExIterator;
+pub use boxed::{BoxedIter, IntoBoxedIters};
+/// # Example
+/// ```
+/// use imex::{imex, IMExIter};
+///
+/// let merged = IMExIter::new(vec!["123456".chars(), "abc".chars()], imex!("0(01){4}"))
+///     .expect("Invalid IMEx")
+///     .collect::<String>();
+///
+/// assert_eq!(merged, "12a3b4c5");
+/// ```
+///
+/// An invalid IMEx, like one with a group that isn't closed, fails to compile:
+/// ```compile_fail
+/// let pattern = imex::imex!("0(01");
+/// ```
+#[cfg(feature = "macros")]
+pub use imex_macros::imex;
+pub use iter::{IMExIter, StrictIMExIter};
+use iter::{IMExIterator, Sources, Step};
 pub use merges::IMExMerges;
+pub use pattern::{IntoPattern, Pattern};
+use rng::Rng;
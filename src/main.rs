@@ -1,4 +1,5 @@
-use imex::IMExMerges;
+use imex::{IMExIter, IMExMerges, Pattern};
+use std::convert::TryFrom;
 use std::fs::File;
 use std::io::{stdin, BufRead, BufReader, Lines};
 use std::path::Path;
@@ -14,7 +15,9 @@ or Iterator-Merging-Expression, for controlling the merge.
 
 Documentation for writing an IMEx can be found at https://docs.rs/crate/imex
 In this case, the digits of the IMEx will refer to one of the files you provide
-in the order you provide them, 0-indexed.
+in the order you provide them, 0-indexed. Files past the tenth can be referred
+to with bracketed indices, like [12]. Sorted merges, like <01>, order lines
+alphabetically.
 
 The filename '-' is reserved for stdin.",
         )
@@ -22,9 +25,9 @@ The filename '-' is reserved for stdin.",
         .version(crate_version!())
         .arg(
             Arg::with_name("FILES")
-                .help("Paths of files to be merged. Maximum of 10.")
+                .help("Paths of files to be merged.")
                 .required(true)
-                .max_values(10)
+                .multiple(true)
                 .index(1),
         )
         .arg(
@@ -38,6 +41,30 @@ number of files provided minus one.",
                 .long("imex")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("PARAM")
+                .help(
+                    "Bind a parameter of the IMEx, written
+like {$name}, to a count, as in -D name=5.",
+                )
+                .short("D")
+                .requires("IMEX")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("SEED")
+                .help(
+                    "Seed the random choices of the IMEx,
+like (0%70|1%30), to make the merge
+reproducible.",
+                )
+                .short("s")
+                .long("seed")
+                .requires("IMEX")
+                .takes_value(true),
+        )
         .get_matches();
 
     let mut vec_lines: Vec<Lines<Box<dyn BufRead>>> = matches
@@ -56,14 +83,46 @@ number of files provided minus one.",
         })
         .collect();
 
-    let first = vec_lines.remove(0);
+    let params = matches
+        .values_of("PARAM")
+        .into_iter()
+        .flatten()
+        .map(|param| {
+            let mut parts = param.splitn(2, '=');
+            match (parts.next(), parts.next().map(str::parse::<usize>)) {
+                (Some(name), Some(Ok(count))) => (name, count),
+                _ => panic!("Invalid parameter, expected name=count: {}", param),
+            }
+        })
+        .collect::<Vec<(&str, usize)>>();
+
+    let seed = matches
+        .value_of("SEED")
+        .map(|seed| match seed.parse::<u64>() {
+            Ok(seed) => seed,
+            Err(_) => panic!("Invalid seed, expected a number: {}", seed),
+        });
 
     let imex = match matches.value_of("IMEX") {
-        Some(imex) => match first.imex_merge_all(&mut vec_lines, imex) {
-            Ok(res) => res,
+        Some(imex) => match Pattern::try_from(imex)
+            .map_err(|e| e.render(imex))
+            .and_then(|pattern| {
+                let pattern = pattern.with_params(params).map_err(|e| e.to_string())?;
+                IMExIter::sorted_by(vec_lines, pattern, |a, b| {
+                    a.as_ref().ok().cmp(&b.as_ref().ok())
+                })
+                .map_err(|e| e.to_string())
+            }) {
+            Ok(res) => match seed {
+                Some(seed) => res.seeded(seed),
+                None => res,
+            },
             Err(e) => panic!("Invalid IMEx\n{}", e),
         },
-        None => first.rot_merge_all(&mut vec_lines),
+        None => {
+            let first = vec_lines.remove(0);
+            first.rot_merge_all(&mut vec_lines)
+        }
     };
 
     for line in imex {
@@ -1,4 +1,4 @@
-use crate::IMExIter;
+use crate::{BoxedIter, IMExIter, IntoBoxedIters};
 use std::io::Result;
 
 /// Trait for merging iterators into an [`IMExIter`](./struct.IMExIter.html)
@@ -12,7 +12,8 @@ where
     /// In practice, this is used to merge more than two iterators with a custom IMEx.
     ///
     /// # Error
-    /// Results in an error if the provided IMEx is invalid.
+    /// Results in an error if the provided IMEx is invalid, if it refers to iterators by name, or
+    /// if it has sorted merges, which [`sorted_merge_by`](#method.sorted_merge_by) gives instead.
     ///
     /// # Example
     /// ```
@@ -42,25 +43,89 @@ where
     ///
     /// assert_eq!(merged, "1a!2b@3c#4defg");
     /// ```
+    ///
+    /// This also works for more than ten iterators.
+    /// ```
+    /// use imex::IMExMerges;
+    ///
+    /// let merged = vec!['0']
+    ///     .into_iter()
+    ///     .rot_merge_all(&mut "123456789ab".chars().map(|c| vec![c, c].into_iter()).collect())
+    ///     .collect::<String>();
+    ///
+    /// assert_eq!(merged, "0123456789ab123456789ab");
+    /// ```
     fn rot_merge_all(self, iters: &mut Vec<T>) -> IMExIter<T, I>
     where
         Self: Sized,
     {
-        let iter_count = (iters.len() + 1) as u8;
-        let imex = format!(
-            "({})*",
-            (0..iter_count)
-                .map(|x| format!("{}", x))
-                .collect::<String>()
-        );
+        let imex = rotating_imex(iters.len() + 1);
         self.imex_merge_all(iters, &imex)
             .expect("Default imex should have been valid, but wasn't")
     }
 
+    /// Merges self with a tuple of iterators of other types using a custom IMEx. The iterators
+    /// only need to give the same type of item as self, since they're all boxed into
+    /// [`BoxedIter`](./type.BoxedIter.html)s. Like the other merges, self is iterator 0 of the
+    /// IMEx, and the tuple's iterators follow it.
+    ///
+    /// # Error
+    /// Results in an error if the provided IMEx is invalid, if it refers to iterators by name, or
+    /// if it has sorted merges, which [`sorted_merge_by`](#method.sorted_merge_by) gives instead.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExMerges;
+    ///
+    /// let merged = "123"
+    ///     .chars()
+    ///     .imex_merge_mixed((vec!['a', 'b'], "!@".chars().rev()), "(012)*")
+    ///     .expect("Invalid IMEx")
+    ///     .collect::<String>();
+    ///
+    /// assert_eq!(merged, "1a@2b!3");
+    /// ```
+    fn imex_merge_mixed<'a, U>(self, others: U, imex: &str) -> Result<IMExIter<BoxedIter<'a, I>, I>>
+    where
+        Self: Iterator<Item = I> + Sized + 'a,
+        U: IntoBoxedIters<'a, I>,
+    {
+        let mut iters: Vec<BoxedIter<'a, I>> = vec![Box::new(self)];
+        iters.append(&mut others.into_boxed_iters());
+        IMExIter::new(iters, imex)
+    }
+
+    /// Merges self with a tuple of iterators of other types using a rotating IMEx, like
+    /// [`rot_merge_all`](#method.rot_merge_all).
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExMerges;
+    ///
+    /// let merged = vec![1, 2, 3]
+    ///     .into_iter()
+    ///     .rot_merge_mixed((4..6, Some(6)))
+    ///     .collect::<Vec<i32>>();
+    ///
+    /// assert_eq!(merged, vec![1, 4, 6, 2, 5, 3]);
+    /// ```
+    fn rot_merge_mixed<'a, U>(self, others: U) -> IMExIter<BoxedIter<'a, I>, I>
+    where
+        Self: Iterator<Item = I> + Sized + 'a,
+        U: IntoBoxedIters<'a, I>,
+    {
+        let mut iters: Vec<BoxedIter<'a, I>> = vec![Box::new(self)];
+        iters.append(&mut others.into_boxed_iters());
+        let imex = rotating_imex(iters.len());
+        IMExIter::new(iters, imex.as_str())
+            .expect("Default imex should have been valid, but wasn't")
+    }
+
     /// Merges two iterators (self and other) using a custom IMEx.
     ///
     /// # Error
-    /// Results in an error if the provided IMEx is invalid.
+    /// Results in an error if the provided IMEx is invalid, if it refers to iterators by name, or
+    /// if it has sorted merges, which [`sorted_merge_by`](#method.sorted_merge_by) gives instead.
     ///
     /// # Example
     /// ```
@@ -81,6 +146,42 @@ where
         self.imex_merge_all(&mut vec![other], imex)
     }
 
+    /// Merges many sorted iterators into one sorted iterator, using a sorted merge IMEx. Items are
+    /// ordered by the keys that `key_fn` gives for them, and ties go to the iterator that comes
+    /// first.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::IMExMerges;
+    ///
+    /// let merged = vec![1, 4, 7]
+    ///     .into_iter()
+    ///     .sorted_merge_by(
+    ///         &mut vec![vec![2, 5, 8].into_iter(), vec![3, 6].into_iter()],
+    ///         |n: &i32| *n,
+    ///     )
+    ///     .collect::<Vec<i32>>();
+    ///
+    /// assert_eq!(merged, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    /// ```
+    fn sorted_merge_by<K, F>(self, iters: &mut Vec<T>, key_fn: F) -> IMExIter<T, I>
+    where
+        Self: Sized + Into<T>,
+        K: Ord,
+        F: Fn(&I) -> K + 'static,
+    {
+        let mut total_iters = vec![self.into()];
+        total_iters.append(iters);
+        let imex = format!(
+            "<{}>*",
+            (0..total_iters.len())
+                .map(|x| format!("[{}]", x))
+                .collect::<String>()
+        );
+        IMExIter::sorted_by_key(total_iters, imex.as_str(), key_fn)
+            .expect("Default imex should have been valid, but wasn't")
+    }
+
     /// Merges two iterators (self and other) using an alternating IMEx. The resulting iterator
     /// will consume 1 item from self and other, alternating, until they are both exhausted.
     ///
@@ -104,6 +205,16 @@ where
     }
 }
 
+/// An IMEx that rotates through `iter_count` iterators until they are all exhausted.
+fn rotating_imex(iter_count: usize) -> String {
+    format!(
+        "({})*",
+        (0..iter_count)
+            .map(|x| format!("[{}]", x))
+            .collect::<String>()
+    )
+}
+
 impl<T, I> IMExMerges<T, I> for T
 where
     T: Iterator<Item = I>,
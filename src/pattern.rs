@@ -0,0 +1,232 @@
+use crate::{
+    engine::Program,
+    expression::{IMEx, IndexSpans, ParseError},
+};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io::{Error, ErrorKind::InvalidInput, Result},
+    sync::Arc,
+};
+
+/// A parsed [`IMEx`](./expression/struct.IMEx.html) that can be used by any number of merges,
+/// without being parsed again. Patterns are cheap to clone, since clones share the same parsed
+/// IMEx, and they can be shared between threads. The IMEx is compiled once, when the Pattern is
+/// constructed, into the form that merges run.
+///
+/// # Example
+/// ```
+/// use imex::{IMExIter, Pattern};
+///
+/// let pattern = Pattern::new("(001)*").expect("Invalid IMEx");
+///
+/// let first = IMExIter::new(vec!["1234".chars(), "abcde".chars()], &pattern)
+///     .expect("Invalid IMEx")
+///     .collect::<String>();
+/// let second = IMExIter::new(vec!["5678".chars(), "fghij".chars()], &pattern)
+///     .expect("Invalid IMEx")
+///     .collect::<String>();
+///
+/// assert_eq!(first, "12a34bcde");
+/// assert_eq!(second, "56f78ghij");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    imex: Arc<IMEx>,
+    program: Arc<Program>,
+    source: Option<Arc<Source>>,
+}
+
+/// The string that a Pattern was parsed from, and the spans of the indices written in it.
+#[derive(Debug)]
+pub(crate) struct Source {
+    pub(crate) imex: String,
+    pub(crate) indices: IndexSpans,
+}
+
+impl Pattern {
+    /// Parses a Pattern from an IMEx string.
+    ///
+    /// # Error
+    /// Results in an error if the IMEx is invalid.
+    pub fn new(imex: &str) -> Result<Pattern> {
+        Ok(Pattern::parse(imex)?)
+    }
+
+    fn parse(imex_str: &str) -> std::result::Result<Pattern, ParseError> {
+        let (imex, indices) = IMEx::parse_spanned(imex_str)?;
+        let source = Source {
+            imex: imex_str.to_string(),
+            indices,
+        };
+        Ok(Pattern {
+            source: Some(Arc::new(source)),
+            ..Pattern::from(imex)
+        })
+    }
+
+    /// Returns a Pattern with the parameters of this one, written like `{$name}`, bound to the
+    /// counts in `params`. The parameters can be provided by anything that iterates over
+    /// `(name, count)` pairs, like a `HashMap` or a vector of tuples. Any constructor of
+    /// [`IMExIter`](./struct.IMExIter.html) accepts the bound Pattern, including
+    /// [`IMExIter::sorted_by`](./struct.IMExIter.html#method.sorted_by).
+    ///
+    /// # Error
+    /// Results in an error if the IMEx has a parameter that wasn't provided, or if the same
+    /// parameter was provided twice.
+    ///
+    /// # Example
+    /// ```
+    /// use imex::{IMExIter, Pattern};
+    ///
+    /// let pattern = Pattern::new("<01>{$n}1")
+    ///     .and_then(|pattern| pattern.with_params(vec![("n", 2)]))
+    ///     .expect("Invalid IMEx");
+    /// let merged = IMExIter::sorted_by(vec!["ac".chars(), "bd".chars()], pattern, Ord::cmp)
+    ///     .expect("Invalid IMEx")
+    ///     .collect::<String>();
+    ///
+    /// assert_eq!(merged, "abd");
+    /// ```
+    pub fn with_params<P, S>(&self, params: P) -> Result<Pattern>
+    where
+        P: IntoIterator<Item = (S, usize)>,
+        S: AsRef<str>,
+    {
+        let mut bound = HashMap::new();
+        for (name, count) in params {
+            if bound.insert(name.as_ref().to_string(), count).is_some() {
+                return Err(Error::new(
+                    InvalidInput,
+                    format!("Parameter \"{}\" was provided twice", name.as_ref()),
+                ));
+            }
+        }
+        match self.imex.first_param() {
+            Some(_) => Ok(Pattern::from(self.imex().clone().bind_params(&bound)?)),
+            None => Ok(self.clone()),
+        }
+    }
+
+    /// Returns an immutable reference to the parsed IMEx.
+    pub fn imex(&self) -> &IMEx {
+        &self.imex
+    }
+
+    /// Returns the string that the Pattern was parsed from, or None if it was built from an IMEx.
+    pub(crate) fn source(&self) -> Option<&Source> {
+        self.source.as_deref()
+    }
+
+    /// Returns the compiled form of the IMEx.
+    pub(crate) fn program(&self) -> &Program {
+        &self.program
+    }
+}
+
+impl From<IMEx> for Pattern {
+    fn from(imex: IMEx) -> Pattern {
+        Pattern {
+            program: Arc::new(Program::compile(&imex)),
+            imex: Arc::new(imex),
+            source: None,
+        }
+    }
+}
+
+/// Patterns are equal if their IMExes are, whether or not they were parsed from the same string.
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.imex == other.imex
+    }
+}
+
+impl TryFrom<&str> for Pattern {
+    type Error = ParseError;
+
+    fn try_from(imex: &str) -> std::result::Result<Pattern, ParseError> {
+        Pattern::parse(imex)
+    }
+}
+
+/// Conversion into a [`Pattern`](./struct.Pattern.html), used by the constructors of
+/// [`IMExIter`](./struct.IMExIter.html) so they accept either an IMEx string or a Pattern.
+pub trait IntoPattern {
+    /// Converts self into a Pattern.
+    ///
+    /// # Error
+    /// Results in an error if self is an invalid IMEx string.
+    fn into_pattern(self) -> Result<Pattern>;
+}
+
+impl IntoPattern for &str {
+    fn into_pattern(self) -> Result<Pattern> {
+        Pattern::new(self)
+    }
+}
+
+impl IntoPattern for &String {
+    fn into_pattern(self) -> Result<Pattern> {
+        Pattern::new(self)
+    }
+}
+
+impl IntoPattern for Pattern {
+    fn into_pattern(self) -> Result<Pattern> {
+        Ok(self)
+    }
+}
+
+impl IntoPattern for &Pattern {
+    fn into_pattern(self) -> Result<Pattern> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn pattern_is_send_and_sync() {
+        assert_send_sync::<Pattern>();
+    }
+
+    #[test]
+    fn pattern_clones_share_imex() -> Result<()> {
+        let pattern = Pattern::new("(01)*")?;
+        let clone = pattern.clone();
+
+        assert!(std::ptr::eq(pattern.imex(), clone.imex()));
+        assert_eq!(pattern.imex(), &IMEx::try_from("(01)*")?);
+        Ok(())
+    }
+
+    #[test]
+    fn into_pattern_accepts_strings_and_patterns() -> Result<()> {
+        let pattern = Pattern::new("0{2}1")?;
+
+        assert_eq!("0{2}1".into_pattern()?, pattern);
+        assert_eq!((&"0{2}1".to_string()).into_pattern()?, pattern);
+        assert_eq!((&pattern).into_pattern()?, pattern);
+        assert!("0{".into_pattern().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn with_params_binds_every_param() -> Result<()> {
+        let pattern = Pattern::new("(0{$a}1{$b})*")?;
+
+        assert_eq!(
+            pattern.with_params(vec![("b", 1), ("a", 2)])?,
+            Pattern::new("(0{2}1)*")?
+        );
+        assert!(pattern.with_params(vec![("a", 2)]).is_err());
+        assert!(pattern
+            .with_params(vec![("a", 2), ("b", 1), ("a", 3)])
+            .is_err());
+        Ok(())
+    }
+}
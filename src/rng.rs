@@ -0,0 +1,72 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// A small pseudo-random number generator, used to make weighted random choices while merging.
+/// It isn't suitable for cryptography, but the same seed always gives the same numbers.
+///
+/// This is an implementation of SplitMix64.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Constructs an Rng that gives the same numbers each time it is constructed with `seed`.
+    pub(crate) fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// Constructs an Rng with a seed that is different each time.
+    pub(crate) fn from_entropy() -> Rng {
+        Rng::new(RandomState::new().build_hasher().finish())
+    }
+
+    /// Returns the next pseudo-random number.
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a pseudo-random number that is less than `bound`, or 0 if `bound` is 0.
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(bound)) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_numbers() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_numbers() {
+        let mut a = Rng::new(1);
+        let mut b = Rng::new(2);
+
+        assert!((0..10).any(|_| a.next_u64() != b.next_u64()));
+    }
+
+    #[test]
+    fn below_stays_below_bound() {
+        let mut rng = Rng::new(7);
+
+        for bound in 1..100 {
+            assert!(rng.below(bound) < bound);
+        }
+        assert_eq!(rng.below(0), 0);
+    }
+}
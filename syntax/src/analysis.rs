@@ -0,0 +1,351 @@
+//! Methods for asking a parsed [`IMEx`](../struct.IMEx.html) about the merges it would perform,
+//! without running one. They let a pattern be checked against the iterators it will be given, up
+//! front.
+//!
+//! Every IMEx terminates on finite iterators: a repetition that takes nothing ends its
+//! quantifier, so `*` and `+` stop once the iterators they take from are exhausted.
+//! [`is_bounded`](../struct.IMEx.html#method.is_bounded) answers the stronger question, of
+//! whether an IMEx terminates even if its iterators are infinite.
+//!
+//! Named values, like `a`, and Calls that haven't been resolved aren't indices, so they aren't
+//! counted by [`referenced_indices`](../struct.IMEx.html#method.referenced_indices), and a Param
+//! quantifier that hasn't been bound allows no repetitions, as when merging.
+//!
+//! # Example
+//! ```
+//! use imex_syntax::IMEx;
+//! use std::convert::TryFrom;
+//!
+//! let imex = IMEx::try_from("0{2}(1[-3]|<24>)*").expect("Invalid IMEx");
+//!
+//! assert_eq!(imex.referenced_indices().into_iter().collect::<Vec<_>>(), [-3, 0, 1, 2, 4]);
+//! assert_eq!(imex.max_index(), Some(4));
+//! assert_eq!(imex.arity(), 5);
+//! assert!(!imex.is_bounded());
+//! assert_eq!(imex.max_items(), None);
+//! ```
+use crate::{IMEx, IMExVal, QuantifiedIMExVal, Quantifier};
+use std::collections::BTreeSet;
+
+impl IMEx {
+    /// Returns the indices of the iterators that this IMEx refers to, in order. Negative indices
+    /// are included as they're written, since they count back from the last iterator.
+    pub fn referenced_indices(&self) -> BTreeSet<isize> {
+        let mut indices = BTreeSet::new();
+        self.add_indices(&mut indices);
+        indices
+    }
+
+    fn add_indices(&self, indices: &mut BTreeSet<isize>) {
+        self.vals
+            .iter()
+            .for_each(|val| val.get_val().add_indices(indices));
+    }
+
+    /// Returns each index that this IMEx refers to, in the order they're written, with the byte
+    /// offset where they're written in the IMEx as it's printed. Indices within a Call aren't
+    /// included, since a Call isn't printed with the IMEx of the group it calls.
+    pub fn index_positions(&self) -> Vec<(isize, usize)> {
+        let mut positions = vec![];
+        self.add_positions(0, &mut positions);
+        positions
+    }
+
+    /// Adds the positions of the indices in this IMEx, printed at `offset`, to `positions`.
+    /// Returns the offset after it.
+    fn add_positions(&self, offset: usize, positions: &mut Vec<(isize, usize)>) -> usize {
+        self.vals.iter().fold(offset, |offset, val| {
+            let offset = val.get_val().add_positions(offset, positions);
+            if val.get_val().is_zero_width() {
+                offset
+            } else {
+                offset + val.get_quantifier().to_string().len()
+            }
+        })
+    }
+
+    /// Returns whether this IMEx has a class of every iterator, `.`, which refers to every
+    /// iterator without naming their indices.
+    pub fn refers_to_every_iterator(&self) -> bool {
+        self.vals
+            .iter()
+            .any(|val| val.get_val().refers_to_every_iterator())
+    }
+
+    /// Returns the highest index that this IMEx refers to, or None if it doesn't refer to any.
+    pub fn max_index(&self) -> Option<isize> {
+        self.referenced_indices().into_iter().next_back()
+    }
+
+    /// Returns the number of iterators that this IMEx needs for every index it refers to to
+    /// exist. A negative index `-n` needs `n` iterators.
+    pub fn arity(&self) -> usize {
+        self.referenced_indices()
+            .into_iter()
+            .map(|index| match index {
+                index if index < 0 => index.unsigned_abs(),
+                index => index as usize + 1,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns whether merging with this IMEx terminates even if its iterators are infinite,
+    /// because every value that takes items is repeated a bounded number of times.
+    pub fn is_bounded(&self) -> bool {
+        self.bound(true).is_some()
+    }
+
+    /// Returns the most items that merging with this IMEx can give if every iterator were
+    /// infinite, or None if there's no limit. Discarded items aren't counted, since they aren't
+    /// given.
+    pub fn max_items(&self) -> Option<usize> {
+        self.bound(false)
+    }
+
+    /// The most items that this IMEx can take, counting discarded items only if `discarded`.
+    fn bound(&self, discarded: bool) -> Option<usize> {
+        self.vals.iter().try_fold(0usize, |total, val| {
+            val.bound(discarded)
+                .map(|bound| total.saturating_add(bound))
+        })
+    }
+}
+
+impl QuantifiedIMExVal {
+    /// Returns the most items that this value can give if every iterator were infinite, or None
+    /// if there's no limit.
+    pub fn max_items(&self) -> Option<usize> {
+        self.bound(false)
+    }
+
+    fn bound(&self, discarded: bool) -> Option<usize> {
+        match (
+            self.get_val().bound(discarded),
+            self.get_quantifier().maximum(),
+        ) {
+            (Some(0), _) | (_, Some(0)) => Some(0),
+            (Some(bound), Some(maximum)) => Some(bound.saturating_mul(maximum)),
+            _ => None,
+        }
+    }
+}
+
+impl Quantifier {
+    /// Returns whether this quantifier allows a limited number of repetitions. Infinite (`*`)
+    /// and AtLeastOnce (`+`) are unbounded, as is a Range without a maximum (`{m,}`).
+    pub fn is_bounded(&self) -> bool {
+        self.maximum().is_some()
+    }
+}
+
+impl IMExVal {
+    fn add_indices(&self, indices: &mut BTreeSet<isize>) {
+        match self {
+            IMExVal::Single(index) => {
+                indices.insert(*index);
+            }
+            IMExVal::Class(class) => indices.extend(class.get_indices().unwrap_or_default()),
+            IMExVal::Sorted(sorted) => indices.extend(
+                sorted
+                    .get_sources()
+                    .iter()
+                    .filter_map(IMExVal::source_index),
+            ),
+            IMExVal::Anchor(val) | IMExVal::Discard(val) => val.add_indices(indices),
+            IMExVal::Conditional(conditional) => {
+                conditional.get_source().add_indices(indices);
+                conditional.get_imex().add_indices(indices);
+            }
+            val => val
+                .inner_imexes()
+                .into_iter()
+                .for_each(|imex| imex.add_indices(indices)),
+        }
+    }
+
+    /// Adds the positions of the indices in this value, printed at `offset`, to `positions`.
+    /// Returns the offset after it, so each value is only visited once.
+    fn add_positions(&self, offset: usize, positions: &mut Vec<(isize, usize)>) -> usize {
+        match self {
+            IMExVal::Single(index) => {
+                positions.push((*index, offset));
+                offset + self.to_string().len()
+            }
+            IMExVal::Class(class) => {
+                let mut position = offset + "[".len();
+                for index in class.get_indices().unwrap_or_default() {
+                    positions.push((*index, position));
+                    position += index.to_string().len() + ",".len();
+                }
+                offset + class.to_string().len()
+            }
+            IMExVal::Sorted(sorted) => {
+                let offset = sorted
+                    .get_sources()
+                    .iter()
+                    .fold(offset + "<".len(), |offset, source| {
+                        source.add_positions(offset, positions)
+                    });
+                offset + ">".len()
+            }
+            IMExVal::Anchor(val) => val.add_positions(offset + "$".len(), positions),
+            IMExVal::Discard(val) => val.add_positions(offset + "~".len(), positions),
+            IMExVal::Group(imex) => imex.add_positions(offset + "(".len(), positions) + ")".len(),
+            IMExVal::NamedGroup(name, imex) => {
+                imex.add_positions(offset + "(?<>".len() + name.len(), positions) + ")".len()
+            }
+            IMExVal::Alternation(imexes) => {
+                imexes.iter().fold(offset, |offset, imex| {
+                    imex.add_positions(offset + "(".len(), positions)
+                }) + ")".len()
+            }
+            IMExVal::Weighted(choice) => {
+                choice
+                    .get_alternatives()
+                    .iter()
+                    .fold(offset, |offset, (imex, weight)| {
+                        imex.add_positions(offset + "(".len(), positions)
+                            + format!("%{}", weight).len()
+                    })
+                    + ")".len()
+            }
+            IMExVal::Conditional(conditional) => {
+                let negation = if conditional.is_negated() { "!" } else { "" };
+                let offset = offset + "(?".len() + negation.len();
+                let offset = conditional.get_source().add_positions(offset, positions);
+                conditional
+                    .get_imex()
+                    .add_positions(offset + ":".len(), positions)
+                    + ")".len()
+            }
+            IMExVal::Named(_) | IMExVal::Call(_, _) => offset + self.to_string().len(),
+        }
+    }
+
+    fn refers_to_every_iterator(&self) -> bool {
+        match self {
+            IMExVal::Class(class) => class.get_indices().is_none(),
+            IMExVal::Discard(val) => val.refers_to_every_iterator(),
+            val => val
+                .inner_imexes()
+                .into_iter()
+                .any(IMEx::refers_to_every_iterator),
+        }
+    }
+
+    /// The most items that one use of this value can take, counting discarded items only if
+    /// `discarded`. Alternatives commit to the first one that gives an item, so only the largest
+    /// counts.
+    fn bound(&self, discarded: bool) -> Option<usize> {
+        match self {
+            IMExVal::Single(_) | IMExVal::Named(_) | IMExVal::Class(_) | IMExVal::Sorted(_) => {
+                Some(1)
+            }
+            IMExVal::Anchor(_) => Some(0),
+            IMExVal::Discard(val) if discarded => val.bound(discarded),
+            IMExVal::Discard(_) => Some(0),
+            IMExVal::Conditional(conditional) => conditional.get_imex().bound(discarded),
+            val => val.inner_imexes().into_iter().try_fold(0, |max, imex| {
+                imex.bound(discarded).map(|bound| max.max(bound))
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        random::{self, Numbers},
+        IMEx,
+    };
+    use std::{convert::TryFrom, io::Result};
+
+    #[test]
+    fn referenced_indices_include_every_kind_of_value() -> Result<()> {
+        let imex = IMEx::try_from("0[7](?1:[2,3])$[-2]<45>~[6]a.")?;
+
+        assert_eq!(
+            imex.referenced_indices()
+                .into_iter()
+                .collect::<Vec<isize>>(),
+            [-2, 0, 1, 2, 3, 4, 5, 6, 7]
+        );
+        assert_eq!(imex.max_index(), Some(7));
+        assert_eq!(imex.arity(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn index_positions_are_offsets_in_printed_imex() -> Result<()> {
+        let imex = IMEx::try_from("0[12](?!3:[4,-5])(6%2|<78>%1)$9(?<a>~[10])(&a).")?;
+        let printed = imex.to_string();
+
+        assert_eq!(
+            imex.index_positions(),
+            [
+                (0, 0),
+                (12, 1),
+                (3, 8),
+                (4, 11),
+                (-5, 13),
+                (6, 18),
+                (7, 23),
+                (8, 24),
+                (9, 30),
+                (10, 37),
+            ]
+        );
+        for (index, position) in imex.index_positions() {
+            let written = printed[position..].trim_start_matches('[');
+            assert!(written.starts_with(&index.to_string()), "{}", written);
+        }
+        assert!(imex.refers_to_every_iterator());
+        assert!(!IMEx::try_from("0(1|~[2])")?.refers_to_every_iterator());
+        Ok(())
+    }
+
+    #[test]
+    fn index_positions_of_random_imexes_point_at_indices() {
+        let mut numbers = Numbers::new(41);
+        for _ in 0..500 {
+            let imex = random::imex(&mut numbers, 4);
+            let printed = imex.to_string();
+            for (index, position) in imex.index_positions() {
+                let written = printed[position..].trim_start_matches('[');
+                assert!(written.starts_with(&index.to_string()), "{}", printed);
+            }
+        }
+    }
+
+    #[test]
+    fn arity_counts_negative_indices_from_the_end() -> Result<()> {
+        assert_eq!(IMEx::try_from("0[-3]")?.arity(), 3);
+        assert_eq!(IMEx::try_from("[-1]")?.max_index(), Some(-1));
+        assert_eq!(IMEx::try_from("a.")?.arity(), 0);
+        assert_eq!(IMEx::try_from("")?.max_index(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn max_items_multiplies_repetitions() -> Result<()> {
+        assert_eq!(IMEx::try_from("0{3}1")?.max_items(), Some(4));
+        assert_eq!(IMEx::try_from("(01{2})?(2|34)")?.max_items(), Some(5));
+        assert_eq!(IMEx::try_from("(0%1|1{3}%9){2,4}")?.max_items(), Some(12));
+        assert_eq!(IMEx::try_from("~0{5}1$2(?3:4)")?.max_items(), Some(2));
+        assert_eq!(IMEx::try_from("0*")?.max_items(), None);
+        assert_eq!(IMEx::try_from("(01){2,}")?.max_items(), None);
+        assert_eq!(IMEx::try_from("0{$n}")?.max_items(), Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn discarding_forever_is_unbounded_but_gives_nothing() -> Result<()> {
+        let imex = IMEx::try_from("(~0)*")?;
+
+        assert!(!imex.is_bounded());
+        assert_eq!(imex.max_items(), Some(0));
+        assert!(IMEx::try_from("(0{0})*1")?.is_bounded());
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+//! Functions for constructing an [`IMEx`](../struct.IMEx.html) from code, without writing and
+//! parsing a string. The result is the same IMEx that parsing the equivalent string would give.
+//!
+//! Values are made by the functions of this module, like [`src`](./fn.src.html) and
+//! [`group`](./fn.group.html), then quantified by methods of
+//! [`IMExVal`](../enum.IMExVal.html), like [`times`](../enum.IMExVal.html#method.times) and
+//! [`forever`](../enum.IMExVal.html#method.forever). A value that isn't quantified is used once.
+//!
+//! Functions whose arguments could describe a value that can't be written, like a sorted merge of
+//! groups or a name with spaces, return None for those arguments, so a built IMEx always prints
+//! as a string that parses back to it.
+//!
+//! # Example
+//! ```
+//! use imex_syntax::{builder::*, IMEx};
+//! use std::convert::TryFrom;
+//!
+//! let built = IMEx::seq([src(0).times(3), group([src(1), src(2)]).forever()]);
+//!
+//! assert_eq!(built, IMEx::try_from("0{3}(12)*").expect("Invalid IMEx"));
+//! ```
+use crate::{
+    parse_identifier, Conditional, IMEx, IMExVal, QuantifiedIMExVal, Quantifier, SortedMerge,
+    SourceClass, WeightedChoice, MAX_CLASS_SIZE,
+};
+
+impl IMEx {
+    /// Construct a new IMEx from a sequence of values, which can be quantified or not.
+    pub fn seq<V, Q>(vals: V) -> IMEx
+    where
+        V: IntoIterator<Item = Q>,
+        Q: Into<QuantifiedIMExVal>,
+    {
+        IMEx::new(vals.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Uses a value once, like a value written without a quantifier.
+impl From<IMExVal> for QuantifiedIMExVal {
+    fn from(val: IMExVal) -> QuantifiedIMExVal {
+        val.once()
+    }
+}
+
+impl IMExVal {
+    fn quantified(self, quantifier: Quantifier) -> QuantifiedIMExVal {
+        QuantifiedIMExVal::new(self, quantifier)
+    }
+
+    /// Uses this value once, like a value written without a quantifier.
+    pub fn once(self) -> QuantifiedIMExVal {
+        self.quantified(Quantifier::Finite(1))
+    }
+
+    /// Repeats this value `count` times, like `{x}`.
+    pub fn times(self, count: usize) -> QuantifiedIMExVal {
+        self.quantified(Quantifier::Finite(count))
+    }
+
+    /// Repeats this value until the relevant iterators are exhausted, like `*`.
+    pub fn forever(self) -> QuantifiedIMExVal {
+        self.quantified(Quantifier::Infinite)
+    }
+
+    /// Repeats this value until the relevant iterators are exhausted, requiring at least one
+    /// repetition when merging strictly, like `+`.
+    pub fn at_least_once(self) -> QuantifiedIMExVal {
+        self.quantified(Quantifier::AtLeastOnce)
+    }
+
+    /// Uses this value once, if the relevant iterators aren't exhausted, like `?`.
+    pub fn optional(self) -> QuantifiedIMExVal {
+        self.quantified(Quantifier::Optional)
+    }
+
+    /// Repeats this value at most `max` times, requiring `min` repetitions when merging strictly,
+    /// like `{m,n}`.
+    pub fn between(self, min: usize, max: usize) -> QuantifiedIMExVal {
+        self.quantified(Quantifier::Range(min, Some(max)))
+    }
+
+    /// Repeats this value until the relevant iterators are exhausted, requiring `min`
+    /// repetitions when merging strictly, like `{m,}`.
+    pub fn at_least(self, min: usize) -> QuantifiedIMExVal {
+        self.quantified(Quantifier::Range(min, None))
+    }
+
+    /// Repeats this value at most `max` times, like `{,n}`.
+    pub fn at_most(self, max: usize) -> QuantifiedIMExVal {
+        self.quantified(Quantifier::Range(0, Some(max)))
+    }
+
+    /// Repeats this value the number of times bound to the parameter `name`, like `{$name}`, or
+    /// None if `name` isn't a valid name.
+    pub fn times_param<S: Into<String>>(self, name: S) -> Option<QuantifiedIMExVal> {
+        let name = name.into();
+        if !is_identifier(&name) {
+            return None;
+        }
+        Some(self.quantified(Quantifier::Param(name)))
+    }
+
+    /// Returns a Call to this NamedGroup, like `(&name)`, or None if this isn't a NamedGroup.
+    pub fn call(&self) -> Option<IMExVal> {
+        match self {
+            IMExVal::NamedGroup(name, imex) => Some(IMExVal::Call(name.clone(), imex.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Returns true if `name` can be written as the name of an iterator, group or parameter.
+fn is_identifier(name: &str) -> bool {
+    matches!(parse_identifier(name), Ok(("", _)))
+}
+
+/// Returns true if `val` refers to a single iterator, by index or by name.
+fn is_source(val: &IMExVal) -> bool {
+    matches!(val, IMExVal::Single(_) | IMExVal::Named(_))
+}
+
+/// The iterator at `index`, like `0` or `[12]`.
+pub fn src(index: isize) -> IMExVal {
+    IMExVal::Single(index)
+}
+
+/// The iterator called `name`, like `a` or `[name]`, or None if `name` isn't a valid name.
+pub fn named<S: Into<String>>(name: S) -> Option<IMExVal> {
+    let name = name.into();
+    if !is_identifier(&name) {
+        return None;
+    }
+    Some(IMExVal::Named(name))
+}
+
+/// A class of every iterator, like `.`.
+pub fn any() -> IMExVal {
+    IMExVal::Class(SourceClass::any())
+}
+
+/// A class of the iterators at `indices`, like `[0,2,4]`, or None if there are no indices, too
+/// many, or only a negative one, which can't be written as a class.
+pub fn class<I: IntoIterator<Item = isize>>(indices: I) -> Option<IMExVal> {
+    let indices = indices.into_iter().collect::<Vec<isize>>();
+    match indices.as_slice() {
+        [] => return None,
+        [index] if *index < 0 => return None,
+        indices if indices.len() > MAX_CLASS_SIZE => return None,
+        _ => {}
+    }
+    Some(IMExVal::Class(SourceClass::new(indices)))
+}
+
+/// A sorted merge of the iterators referred to by `sources`, like `<012>`, or None if there are
+/// no sources or one of them isn't a [`src`](./fn.src.html) or [`named`](./fn.named.html).
+pub fn sorted<I: IntoIterator<Item = IMExVal>>(sources: I) -> Option<IMExVal> {
+    let sources = sources.into_iter().collect::<Vec<IMExVal>>();
+    if sources.is_empty() || !sources.iter().all(is_source) {
+        return None;
+    }
+    Some(IMExVal::Sorted(SortedMerge::new(sources)))
+}
+
+/// A group of values, like `(01)`.
+pub fn group<V, Q>(vals: V) -> IMExVal
+where
+    V: IntoIterator<Item = Q>,
+    Q: Into<QuantifiedIMExVal>,
+{
+    IMExVal::Group(IMEx::seq(vals))
+}
+
+/// A group of values called `name`, like `(?<name>01)`, or None if `name` isn't a valid name. It
+/// can be used again with [`IMExVal::call`](../enum.IMExVal.html#method.call).
+pub fn named_group<S, V, Q>(name: S, vals: V) -> Option<IMExVal>
+where
+    S: Into<String>,
+    V: IntoIterator<Item = Q>,
+    Q: Into<QuantifiedIMExVal>,
+{
+    let name = name.into();
+    if !is_identifier(&name) {
+        return None;
+    }
+    Some(IMExVal::NamedGroup(name, IMEx::seq(vals)))
+}
+
+/// A group of alternatives, like `(0|1)`. As when parsing, a single alternative is a plain group,
+/// and so is no alternative, like `()`.
+pub fn alt<I: IntoIterator<Item = IMEx>>(alternatives: I) -> IMExVal {
+    let mut alternatives = alternatives.into_iter().collect::<Vec<IMEx>>();
+    match alternatives.len() {
+        0 => IMExVal::Group(IMEx::new(vec![])),
+        1 => IMExVal::Group(alternatives.remove(0)),
+        _ => IMExVal::Alternation(alternatives),
+    }
+}
+
+/// A weighted random choice between alternatives, like `(0%70|1%30)`, or None if there are no
+/// alternatives or one of them has a weight of 0.
+pub fn weighted<I: IntoIterator<Item = (IMEx, usize)>>(alternatives: I) -> Option<IMExVal> {
+    let alternatives = alternatives.into_iter().collect::<Vec<(IMEx, usize)>>();
+    if alternatives.is_empty() || alternatives.iter().any(|(_, weight)| *weight == 0) {
+        return None;
+    }
+    Some(IMExVal::Weighted(WeightedChoice::new(alternatives)))
+}
+
+/// Discards the items of `val`, like `~0`.
+pub fn discard(val: IMExVal) -> IMExVal {
+    IMExVal::Discard(Box::new(val))
+}
+
+/// Checks that the iterator referred to by `source` is exhausted, like `$0`, or None if `source`
+/// isn't a [`src`](./fn.src.html) or [`named`](./fn.named.html). An anchor takes no items, so it
+/// can't be repeated and is returned already quantified.
+pub fn anchor(source: IMExVal) -> Option<QuantifiedIMExVal> {
+    if !is_source(&source) {
+        return None;
+    }
+    Some(IMExVal::Anchor(Box::new(source)).once())
+}
+
+/// A group of values that is only used if the iterator referred to by `source` isn't exhausted,
+/// like `(?0:01)`, or None if `source` isn't a [`src`](./fn.src.html) or
+/// [`named`](./fn.named.html).
+pub fn when<V, Q>(source: IMExVal, vals: V) -> Option<IMExVal>
+where
+    V: IntoIterator<Item = Q>,
+    Q: Into<QuantifiedIMExVal>,
+{
+    conditional(source, false, vals)
+}
+
+/// A group of values that is only used if the iterator referred to by `source` is exhausted,
+/// like `(?!0:01)`, or None if `source` isn't a [`src`](./fn.src.html) or
+/// [`named`](./fn.named.html).
+pub fn unless<V, Q>(source: IMExVal, vals: V) -> Option<IMExVal>
+where
+    V: IntoIterator<Item = Q>,
+    Q: Into<QuantifiedIMExVal>,
+{
+    conditional(source, true, vals)
+}
+
+fn conditional<V, Q>(source: IMExVal, negated: bool, vals: V) -> Option<IMExVal>
+where
+    V: IntoIterator<Item = Q>,
+    Q: Into<QuantifiedIMExVal>,
+{
+    if !is_source(&source) {
+        return None;
+    }
+    Some(IMExVal::Conditional(Conditional::new(
+        source,
+        negated,
+        IMEx::seq(vals),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{convert::TryFrom, io::Result};
+
+    #[test]
+    fn built_quantifiers_match_parsed() -> Result<()> {
+        let built = IMEx::seq([
+            src(0).once(),
+            src(1).times(3),
+            src(2).forever(),
+            src(3).at_least_once(),
+            src(4).optional(),
+            src(5).between(1, 2),
+            src(6).at_least(2),
+            src(7).at_most(3),
+            src(8).times_param("n").expect("n is a valid name"),
+        ]);
+
+        assert_eq!(built, IMEx::try_from("01{3}2*3+4?5{1,2}6{2,}7{,3}8{$n}")?);
+        Ok(())
+    }
+
+    #[test]
+    fn built_values_match_parsed() -> Result<()> {
+        let built = IMEx::seq([
+            src(12).once(),
+            named("a").expect("a is a valid name").once(),
+            named("long").expect("long is a valid name").forever(),
+            any().times(2),
+            class([0, 2, 4]).expect("class has indices").once(),
+            sorted([src(0), src(1)])
+                .expect("sources are sources")
+                .forever(),
+            discard(src(0)).times(2),
+            anchor(src(1)).expect("src(1) is a source"),
+        ]);
+
+        assert_eq!(
+            built,
+            IMEx::try_from("[12]a[long]*.{2}[0,2,4]<01>*~0{2}$1")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn built_groups_match_parsed() -> Result<()> {
+        let built = IMEx::seq([
+            group([src(0), src(1)]).forever(),
+            alt([IMEx::seq([src(0)]), IMEx::seq([src(1).times(2)])]).once(),
+            alt([IMEx::seq([src(2)])]).once(),
+            alt(Vec::<IMEx>::new()).once(),
+            weighted([(IMEx::seq([src(0)]), 70), (IMEx::seq([src(1)]), 30)])
+                .expect("weights are positive")
+                .forever(),
+            when(src(0), [src(0), src(1)])
+                .expect("src(0) is a source")
+                .once(),
+            unless(named("b").expect("b is a valid name"), [src(2)])
+                .expect("b is a source")
+                .once(),
+        ]);
+
+        assert_eq!(
+            built,
+            IMEx::try_from("(01)*(0|1{2})(2)()(0%70|1%30)*(?0:01)(?!b:2)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn built_calls_match_parsed() -> Result<()> {
+        let pair = named_group("pair", [src(0), src(1)]).expect("pair is a valid name");
+        let call = pair.call().expect("pair is a named group");
+        let built = IMEx::seq([pair.once(), call.forever()]);
+
+        assert_eq!(built, IMEx::try_from("(?<pair>01)(&pair)*")?);
+        assert_eq!(src(0).call(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn seq_accepts_unquantified_values() -> Result<()> {
+        assert_eq!(IMEx::seq([src(0), src(1)]), IMEx::try_from("01")?);
+        assert_eq!(IMEx::seq(Vec::<IMExVal>::new()), IMEx::try_from("")?);
+        Ok(())
+    }
+
+    #[test]
+    fn unwritable_values_are_not_built() {
+        assert_eq!(named("0"), None);
+        assert_eq!(named("two words"), None);
+        assert_eq!(named_group("", [src(0)]), None);
+        assert_eq!(src(0).times_param("$n"), None);
+        assert_eq!(class(Vec::new()), None);
+        assert_eq!(class([-1]), None);
+        assert_eq!(class(0..=MAX_CLASS_SIZE as isize), None);
+        assert_eq!(sorted(Vec::new()), None);
+        assert_eq!(sorted([src(0), group([src(1)])]), None);
+        assert_eq!(weighted(Vec::new()), None);
+        assert_eq!(weighted([(IMEx::seq([src(0)]), 0)]), None);
+        assert_eq!(anchor(any()), None);
+        assert_eq!(when(group([src(0)]), [src(1)]), None);
+        assert_eq!(
+            unless(class([0, 1]).expect("class has indices"), [src(1)]),
+            None
+        );
+    }
+
+    #[test]
+    fn built_values_print_as_they_parse() -> Result<()> {
+        let built = IMEx::seq([
+            class([-2, 0]).expect("class has indices").once(),
+            class([3]).expect("class has an index").forever(),
+            src(-1).once(),
+        ]);
+        assert_eq!(IMEx::try_from(built.to_string().as_str())?, built);
+        Ok(())
+    }
+}
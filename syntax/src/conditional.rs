@@ -0,0 +1,147 @@
+use crate::{parse_source_imex_val, Failure, IMEx, IMExVal, Marked, ParserCombinator, Resolved};
+use nom::{character::complete::char, combinator::opt, sequence::delimited, IResult};
+use std::{collections::HashMap, fmt, io::Result};
+
+/// Represents a conditional group in a parsed [`IMEx`](./struct.IMEx.html), written like
+/// `(?0:01)`. The inner IMEx is only used if the iterator being checked still has items when the
+/// group is reached. A negated conditional group, written like `(?!0:01)`, is only used if the
+/// iterator being checked is exhausted.
+///
+/// The iterator is checked without consuming any of its items.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Conditional {
+    source: Box<IMExVal>,
+    negated: bool,
+    imex: IMEx,
+}
+
+impl Conditional {
+    /// Construct a new Conditional, which checks the iterator referred to by `source`, a Single
+    /// or a Named [`IMExVal`](./enum.IMExVal.html).
+    pub fn new(source: IMExVal, negated: bool, imex: IMEx) -> Conditional {
+        Conditional {
+            source: Box::new(source),
+            negated,
+            imex,
+        }
+    }
+
+    /// Returns an immutable reference to the value referring to the iterator being checked.
+    pub fn get_source(&self) -> &IMExVal {
+        &self.source
+    }
+
+    /// Returns true if the inner IMEx is used when the iterator being checked is exhausted.
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Returns an immutable reference to the inner IMEx.
+    pub fn get_imex(&self) -> &IMEx {
+        &self.imex
+    }
+
+    /// Resolves the names in self.source and self.imex into indices of `names`.
+    pub(crate) fn resolve_names<S: AsRef<str>>(self, names: &[S]) -> Result<Conditional> {
+        Ok(Conditional::new(
+            self.source.resolve_names(names)?,
+            self.negated,
+            self.imex.resolve_names(names)?,
+        ))
+    }
+
+    /// Binds the parameters in self.imex to their counts in `params`.
+    pub(crate) fn bind_params(self, params: &HashMap<String, usize>) -> Result<Conditional> {
+        Ok(Conditional::new(
+            *self.source,
+            self.negated,
+            self.imex.bind_params(params)?,
+        ))
+    }
+
+    /// Resolves the calls to named groups in self.imex.
+    pub(crate) fn resolve_calls(
+        self,
+        definitions: &HashMap<String, IMEx>,
+        callers: &mut Vec<String>,
+    ) -> Resolved<Conditional> {
+        Ok(Conditional::new(
+            *self.source,
+            self.negated,
+            self.imex.resolve_calls(definitions, callers)?,
+        ))
+    }
+}
+
+impl fmt::Display for Conditional {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let negation = if self.negated { "!" } else { "" };
+        write!(f, "(?{}{}:{})", negation, self.source, self.imex)
+    }
+}
+
+impl ParserCombinator for Conditional {
+    fn parse_marked(input: &str) -> IResult<&str, (Conditional, Vec<Marked>), Failure> {
+        let (input, _) = char('(')(input)?;
+        let (input, _) = char('?')(input)?;
+        let (input, negated) = opt(char('!'))(input)?;
+        let (input, (source, mut marks)) = parse_source_imex_val(input)?;
+        let (input, (imex, imex_marks)) =
+            delimited(char(':'), IMEx::parse_marked, char(')'))(input)?;
+        marks.extend(imex_marks);
+        Ok((
+            input,
+            (Conditional::new(source, negated.is_some(), imex), marks),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn conditional_parses() -> Result<()> {
+        assert_eq!(
+            Conditional::parse("(?0:01)*"),
+            Ok((
+                "*",
+                Conditional::new(IMExVal::Single(0), false, IMEx::try_from("01")?)
+            ))
+        );
+        assert_eq!(
+            Conditional::parse("(?![12]:a)"),
+            Ok((
+                "",
+                Conditional::new(IMExVal::Single(12), true, IMEx::try_from("a")?)
+            ))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_conditional_fails() {
+        Conditional::parse("(?:01)").unwrap_err();
+
+        Conditional::parse("(?0)").unwrap_err();
+
+        Conditional::parse("(?01)").unwrap_err();
+
+        Conditional::parse("(?(0):1)").unwrap_err();
+
+        Conditional::parse("(!0:1)").unwrap_err();
+
+        Conditional::parse("(?0:1").unwrap_err();
+    }
+
+    #[test]
+    fn conditional_prints_as_written() -> Result<()> {
+        let conditional = Conditional::new(IMExVal::Single(0), false, IMEx::try_from("01")?);
+        let negated = Conditional::new(IMExVal::Named("ab".to_string()), true, IMEx::try_from("")?);
+
+        assert_eq!(conditional.to_string(), "(?0:01)");
+        assert_eq!(negated.to_string(), "(?![ab]:)");
+        Ok(())
+    }
+}
@@ -0,0 +1,1274 @@
+use crate::{
+    repeated, split_marks, Failure, Mark, Marked, ParseError, ParseErrorKind, ParserCombinator,
+    QuantifiedIMExVal, Quantifier, Resolved,
+};
+use nom::{combinator::all_consuming, combinator::complete, IResult};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    io::Result,
+    ops::{Range, RangeInclusive},
+};
+
+/// A struct representing a parsed [`IMEx`](./struct.IMEx.html). Used by
+/// [`IMExIter`](https://docs.rs/imex/*/imex/struct.IMExIter.html) to perform lazy merging.
+///
+/// An IMEx is never changed by merging, so it can be shared between merges with a
+/// [`Pattern`](https://docs.rs/imex/*/imex/struct.Pattern.html).
+#[derive(PartialEq, Debug, Clone)]
+pub struct IMEx {
+    pub vals: Vec<QuantifiedIMExVal>,
+}
+
+/// The indices written in an IMEx string, each with its span in the string, in the order they're
+/// written. A range in a class, like `0-5`, is written once for all of its indices.
+pub type IndexSpans = Vec<(RangeInclusive<isize>, Range<usize>)>;
+
+impl IMEx {
+    /// Construct a new IMEx from its quantified values. See also
+    /// [`IMEx::seq`](#method.seq), which also accepts unquantified values.
+    pub fn new(vals: Vec<QuantifiedIMExVal>) -> IMEx {
+        IMEx { vals }
+    }
+
+    /// Replaces every named iterator reference in this [`IMEx`](./struct.IMEx.html) with the index
+    /// of that name in `names`, producing an IMEx that only uses indices.
+    ///
+    /// # Error
+    /// Results in an error if the IMEx refers to a name that isn't in `names`.
+    ///
+    /// # Example
+    /// ```
+    /// use imex_syntax::IMEx;
+    /// use std::convert::TryFrom;
+    ///
+    /// let imex = IMEx::try_from("(a{3}[long_b])*")
+    ///     .expect("Invalid IMEx")
+    ///     .resolve_names(&["long_b", "a"])
+    ///     .expect("Unknown name");
+    ///
+    /// assert_eq!(imex, IMEx::try_from("(1{3}0)*").expect("Invalid IMEx"));
+    /// ```
+    pub fn resolve_names<S: AsRef<str>>(self, names: &[S]) -> Result<IMEx> {
+        Ok(IMEx::new(
+            self.vals
+                .into_iter()
+                .map(|val| val.resolve_names(names))
+                .collect::<Result<Vec<QuantifiedIMExVal>>>()?,
+        ))
+    }
+
+    /// Replaces every parameter in this [`IMEx`](./struct.IMEx.html), written like `{$name}`, with
+    /// the count bound to its name in `params`.
+    ///
+    /// # Error
+    /// Results in an error if the IMEx has a parameter that isn't in `params`.
+    ///
+    /// # Example
+    /// ```
+    /// use imex_syntax::IMEx;
+    /// use std::{collections::HashMap, convert::TryFrom};
+    ///
+    /// let mut params = HashMap::new();
+    /// params.insert("burst".to_string(), 3);
+    ///
+    /// let imex = IMEx::try_from("(0{$burst}1)*")
+    ///     .expect("Invalid IMEx")
+    ///     .bind_params(&params)
+    ///     .expect("Unbound parameter");
+    ///
+    /// assert_eq!(imex, IMEx::try_from("(0{3}1)*").expect("Invalid IMEx"));
+    /// ```
+    pub fn bind_params(self, params: &HashMap<String, usize>) -> Result<IMEx> {
+        Ok(IMEx::new(
+            self.vals
+                .into_iter()
+                .map(|val| val.bind_params(params))
+                .collect::<Result<Vec<QuantifiedIMExVal>>>()?,
+        ))
+    }
+
+    /// Returns the name of the first parameter within this IMEx, or None if it has no parameters.
+    pub fn first_param(&self) -> Option<&str> {
+        self.vals.iter().find_map(|val| match val.get_quantifier() {
+            Quantifier::Param(name) => Some(name.as_str()),
+            _ => val
+                .get_val()
+                .inner_imexes()
+                .into_iter()
+                .find_map(IMEx::first_param),
+        })
+    }
+
+    /// Returns the names that this IMEx refers to iterators by, in order, which is empty if it
+    /// only uses indices.
+    pub fn names(&self) -> Vec<&str> {
+        self.vals
+            .iter()
+            .flat_map(|val| val.get_val().names())
+            .collect()
+    }
+
+    /// Returns true if this IMEx has a sorted merge, like `<01>`, anywhere within it.
+    pub fn has_sorted(&self) -> bool {
+        self.vals.iter().any(|val| val.get_val().has_sorted())
+    }
+
+    /// Adds the named groups within this IMEx to `definitions`.
+    pub(crate) fn collect_definitions(
+        &self,
+        definitions: &mut HashMap<String, IMEx>,
+    ) -> Resolved<()> {
+        self.vals
+            .iter()
+            .try_for_each(|val| val.get_val().collect_definitions(definitions))
+    }
+
+    /// Resolves the calls to named groups within this IMEx.
+    pub(crate) fn resolve_calls(
+        self,
+        definitions: &HashMap<String, IMEx>,
+        callers: &mut Vec<String>,
+    ) -> Resolved<IMEx> {
+        Ok(IMEx::new(
+            self.vals
+                .into_iter()
+                .map(|val| val.resolve_calls(definitions, callers))
+                .collect::<Resolved<Vec<QuantifiedIMExVal>>>()?,
+        ))
+    }
+
+    /// Parse an [`IMEx`](./struct.IMEx.html) from a string in extended mode, which ignores
+    /// whitespace and comments between tokens, so long IMExes can be split over several lines. A
+    /// comment starts with `#` and runs to the end of the line. Whitespace can't be written within
+    /// a number or name, so `[1 2]` is an error rather than the index `[12]`.
+    ///
+    /// [`IMEx::try_from`](#impl-TryFrom%3C%26str%3E) also uses extended mode when the string
+    /// starts with the `(?x)` flag.
+    ///
+    /// # Error
+    /// Results in an error if the IMEx is invalid once whitespace and comments are removed, or if
+    /// they split a number or name. The span of the error is within `imex_str`, as it was before
+    /// they were removed.
+    ///
+    /// # Example
+    /// ```
+    /// use imex_syntax::IMEx;
+    /// use std::convert::TryFrom;
+    ///
+    /// let imex = IMEx::parse_extended(
+    ///     "(
+    ///         0{3}  # three from the first iterator
+    ///         1     # then one from the second
+    ///     )*",
+    /// )
+    /// .expect("Invalid IMEx");
+    ///
+    /// assert_eq!(imex, IMEx::try_from("(0{3}1)*").expect("Invalid IMEx"));
+    /// ```
+    pub fn parse_extended(imex_str: &str) -> std::result::Result<IMEx, ParseError> {
+        IMEx::parse_extended_spanned(imex_str).map(|(imex, _)| imex)
+    }
+
+    /// Parse an [`IMEx`](./struct.IMEx.html) from a string like
+    /// [`IMEx::try_from`](#impl-TryFrom%3C%26str%3E), along with the spans of the indices written
+    /// in the string.
+    pub fn parse_spanned(imex_str: &str) -> std::result::Result<(IMEx, IndexSpans), ParseError> {
+        let extended = match imex_str.strip_prefix("(?x)") {
+            Some(extended) => extended,
+            None => return IMEx::parse_resolved(imex_str),
+        };
+        let flag = "(?x)".len();
+        let (imex, indices) = IMEx::parse_extended_spanned(extended)
+            .map_err(|error| error.map_span(|position| position + flag))?;
+        let indices = indices
+            .into_iter()
+            .map(|(written, span)| (written, span.start + flag..span.end + flag))
+            .collect();
+        Ok((imex, indices))
+    }
+
+    /// Parse an [`IMEx`](./struct.IMEx.html) in extended mode, along with the spans of the
+    /// indices written in `imex_str`.
+    fn parse_extended_spanned(
+        imex_str: &str,
+    ) -> std::result::Result<(IMEx, IndexSpans), ParseError> {
+        let mut stripped = String::new();
+        let mut offsets = vec![];
+        // Where the whitespace and comments since the last character that was kept start.
+        let mut gap = None;
+        let mut comment = false;
+        let mut within = None;
+        for (index, c) in imex_str.char_indices() {
+            if comment || c == '#' || c.is_whitespace() {
+                comment = (comment || c == '#') && c != '\n';
+                gap = gap.or(Some(index));
+                continue;
+            }
+            // A single space is kept where the gap would otherwise join a number or name to what
+            // follows it, so that parsing fails there.
+            if let Some(gap) = gap.take() {
+                if within.is_some() && continues_token(&stripped, c) {
+                    stripped.push(' ');
+                    offsets.push(gap);
+                }
+            }
+            within = match within {
+                Some('%') if is_word(c) => within,
+                Some(close) if close != '%' && c != close => within,
+                _ => opened_by(&stripped, c),
+            };
+            stripped.push(c);
+            offsets.extend((0..c.len_utf8()).map(|byte| index + byte));
+        }
+        let end = offsets.last().map_or(0, |offset| offset + 1);
+        let (imex, indices) = IMEx::parse_resolved(&stripped).map_err(|error| {
+            error.map_span(|position| offsets.get(position).copied().unwrap_or(end))
+        })?;
+        // An index is never empty, so its span ends just after its last byte, rather than where
+        // whatever follows it starts.
+        let indices = indices
+            .into_iter()
+            .map(|(written, span)| (written, offsets[span.start]..offsets[span.end - 1] + 1))
+            .collect();
+        Ok((imex, indices))
+    }
+
+    /// Parse an [`IMEx`](./struct.IMEx.html) from a string, then resolve its calls to named
+    /// groups.
+    fn parse_resolved(imex_str: &str) -> std::result::Result<(IMEx, IndexSpans), ParseError> {
+        let (imex, marks) = match IMEx::parse_complete(imex_str) {
+            Ok((_, parsed)) => parsed,
+            Err(nom::Err::Error(failure)) | Err(nom::Err::Failure(failure)) => {
+                return Err(ParseError::from_failure(imex_str, Some(failure)))
+            }
+            Err(nom::Err::Incomplete(_)) => return Err(ParseError::from_failure(imex_str, None)),
+        };
+        let mut definitions = HashMap::new();
+        let imex = imex
+            .collect_definitions(&mut definitions)
+            .and_then(|_| imex.resolve_calls(&definitions, &mut vec![]))
+            .map_err(|kind| {
+                let marked = |mark: Mark| {
+                    marks
+                        .iter()
+                        .filter(move |marked| marked.mark == mark)
+                        .map(|marked| marked.span(imex_str))
+                };
+                let span = match &kind {
+                    // The first definition of a name is fine, so the second one is wrong.
+                    ParseErrorKind::DuplicateGroup(name) => {
+                        marked(Mark::Definition(name.clone())).nth(1)
+                    }
+                    ParseErrorKind::UndefinedGroup(name) | ParseErrorKind::RecursiveGroup(name) => {
+                        marked(Mark::Call(name.clone())).next()
+                    }
+                    _ => None,
+                };
+                ParseError::new(kind, span.unwrap_or(0..imex_str.len()), vec![])
+            })?;
+        let indices = marks
+            .iter()
+            .filter_map(|marked| match &marked.mark {
+                Mark::Indices(written) => Some((written.clone(), marked.span(imex_str))),
+                _ => None,
+            })
+            .collect();
+        Ok((imex, indices))
+    }
+
+    /// Parser combinator for parsing an [`IMEx`](./struct.IMEx.html), making use of the
+    /// nom library.
+    ///
+    /// Currently, this parser combinator expects to be "all consuming", which means it will fail if
+    /// there is any input string remaining after parsing an IMEx.
+    ///
+    /// # Error
+    /// Results in an error if the input string is not a valid IMEx.
+    fn parse_complete(input: &str) -> IResult<&str, (IMEx, Vec<Marked>), Failure> {
+        complete(all_consuming(IMEx::parse_marked))(input)
+    }
+}
+
+impl TryFrom<&str> for IMEx {
+    type Error = ParseError;
+
+    /// Parse an [`IMEx`](./struct.IMEx.html) from a string.
+    ///
+    /// # Error
+    /// Results in a [`ParseError`](./struct.ParseError.html) if the IMEx is invalid, or if it
+    /// calls a named group that isn't defined, or that would call itself. The error holds the
+    /// span of the IMEx where it went wrong, so it can be rendered as a diagnostic.
+    ///
+    /// If the string starts with `(?x)`, the rest of it is parsed in extended mode, as with
+    /// [`IMEx::parse_extended`](#method.parse_extended).
+    ///
+    /// # Example
+    /// ```
+    /// use imex_syntax::IMEx;
+    /// use std::convert::TryFrom;
+    /// let imex = IMEx::try_from("01*(23){4}");
+    /// ```
+    fn try_from(imex_str: &str) -> std::result::Result<Self, ParseError> {
+        IMEx::parse_spanned(imex_str).map(|(imex, _)| imex)
+    }
+}
+
+/// Prints the IMEx in a canonical form, which parses back into an equal IMEx.
+///
+/// # Example
+/// ```
+/// use imex_syntax::IMEx;
+/// use std::convert::TryFrom;
+///
+/// let imex = IMEx::try_from("0{1}(1|[2])*[0-2]").expect("Invalid IMEx");
+///
+/// assert_eq!(imex.to_string(), "0(1|2)*[0,1,2]");
+/// assert_eq!(IMEx::try_from(imex.to_string().as_str()).ok(), Some(imex));
+/// ```
+impl fmt::Display for IMEx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.vals.iter().try_for_each(|val| write!(f, "{}", val))
+    }
+}
+
+impl ParserCombinator for IMEx {
+    fn parse_marked(input: &str) -> IResult<&str, (IMEx, Vec<Marked>), Failure> {
+        let (input, vals) = repeated(QuantifiedIMExVal::parse_marked)(input)?;
+        let (vals, marks) = split_marks(vals);
+        Ok((input, (IMEx::new(vals), marks)))
+    }
+}
+
+/// Whether `c` can be part of a number or name in an IMEx.
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Returns the character that closes the part of an IMEx opened by `c`, following the characters
+/// `before` it, if letters and digits written next to each other within that part form a single
+/// number or name. That's the case within classes, quantifiers, named groups, calls and weights.
+/// A weight is returned as `%`, since it's closed by whatever follows its digits.
+fn opened_by(before: &str, c: char) -> Option<char> {
+    match c {
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '%' => Some('%'),
+        '<' if before.ends_with("(?") => Some('>'),
+        '&' if before.ends_with('(') => Some(')'),
+        _ => None,
+    }
+}
+
+/// Whether `c` would continue the number or name at the end of `before`, including the digits
+/// after the sign of a negative index.
+fn continues_token(before: &str, c: char) -> bool {
+    let mut chars = before.chars().rev();
+    match chars.next() {
+        Some('-') => c.is_ascii_digit() && !chars.next().map_or(false, is_word),
+        Some(last) => is_word(last) && is_word(c),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        random::{self, Numbers},
+        Conditional, IMExVal, ParseErrorKind, Quantifier, SortedMerge, SourceClass, WeightedChoice,
+    };
+    use super::*;
+
+    #[test]
+    fn empty_string_gives_empty_group_imex() -> Result<()> {
+        let i = IMEx::try_from("")?;
+
+        assert_eq!(i, IMEx::new(vec![]));
+        Ok(())
+    }
+
+    #[test]
+    fn repeats_gives_repeating_imex() -> Result<()> {
+        let i = IMEx::try_from("13{3}9*1")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1),),
+                QuantifiedIMExVal::new(IMExVal::Single(3), Quantifier::Finite(3),),
+                QuantifiedIMExVal::new(IMExVal::Single(9), Quantifier::Infinite,),
+                QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1),),
+            ],)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ranges_give_range_imex() -> Result<()> {
+        let i = IMEx::try_from("1{2,5}3{2,}(9){,4}")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Range(2, Some(5)),),
+                QuantifiedIMExVal::new(IMExVal::Single(3), Quantifier::Range(2, None),),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![QuantifiedIMExVal::new(
+                        IMExVal::Single(9),
+                        Quantifier::Finite(1),
+                    )])),
+                    Quantifier::Range(0, Some(4)),
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn plus_and_question_mark_give_imex() -> Result<()> {
+        let i = IMEx::try_from("1+(2)?")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::AtLeastOnce),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![QuantifiedIMExVal::new(
+                        IMExVal::Single(2),
+                        Quantifier::Finite(1),
+                    )])),
+                    Quantifier::Optional,
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stacked_quantifiers_fails() {
+        IMEx::try_from("1+?").unwrap_err();
+
+        IMEx::try_from("1*+").unwrap_err();
+
+        IMEx::try_from("1?{2}").unwrap_err();
+
+        IMEx::try_from("+1").unwrap_err();
+    }
+
+    #[test]
+    fn bad_ranges_fails() {
+        IMEx::try_from("0{,}").unwrap_err();
+
+        IMEx::try_from("0{5,2}").unwrap_err();
+
+        IMEx::try_from("0{1,2,3}").unwrap_err();
+
+        IMEx::try_from("0{-1,2}").unwrap_err();
+
+        IMEx::try_from("0{1 ,2}").unwrap_err();
+    }
+
+    #[test]
+    fn parens_gives_group_imex() -> Result<()> {
+        let i = IMEx::try_from("1(1)(9)*(4){45}(1(1))()")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1),),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![QuantifiedIMExVal::new(
+                        IMExVal::Single(1),
+                        Quantifier::Finite(1),
+                    )])),
+                    Quantifier::Finite(1),
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![QuantifiedIMExVal::new(
+                        IMExVal::Single(9),
+                        Quantifier::Finite(1),
+                    )])),
+                    Quantifier::Infinite,
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![QuantifiedIMExVal::new(
+                        IMExVal::Single(4),
+                        Quantifier::Finite(1),
+                    )])),
+                    Quantifier::Finite(45),
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![
+                        QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1),),
+                        QuantifiedIMExVal::new(
+                            IMExVal::Group(IMEx::new(vec![QuantifiedIMExVal::new(
+                                IMExVal::Single(1),
+                                Quantifier::Finite(1),
+                            )])),
+                            Quantifier::Finite(1),
+                        )
+                    ])),
+                    Quantifier::Finite(1),
+                ),
+                QuantifiedIMExVal::new(IMExVal::Group(IMEx::new(vec![])), Quantifier::Finite(1),)
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deeply_nested_groups_parse_in_linear_time() -> Result<()> {
+        // Each level used to be parsed again as every kind of group it could be, doubling the
+        // time taken per level.
+        let depth = 64;
+        let open = "(".repeat(depth);
+        let close = ")".repeat(depth);
+        let mut nested = IMEx::new(vec![QuantifiedIMExVal::new(
+            IMExVal::Single(0),
+            Quantifier::Finite(1),
+        )]);
+        for _ in 0..depth {
+            nested = IMEx::new(vec![QuantifiedIMExVal::new(
+                IMExVal::Group(nested),
+                Quantifier::Finite(1),
+            )]);
+        }
+
+        assert_eq!(
+            IMEx::try_from(format!("{}0{}", open, close).as_str())?,
+            nested
+        );
+        IMEx::try_from(format!("{}0|1%2{}", open, close).as_str()).unwrap_err();
+        IMEx::try_from(format!("{}0", open).as_str()).unwrap_err();
+        let weighted = format!("{}0{}", open, "%1)".repeat(depth));
+        assert_eq!(IMEx::try_from(weighted.as_str())?.to_string(), weighted);
+        Ok(())
+    }
+
+    #[test]
+    fn brackets_give_multi_digit_single_imex() -> Result<()> {
+        let i = IMEx::try_from("[12][-1]{2}([007]0)*")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(IMExVal::Single(12), Quantifier::Finite(1),),
+                QuantifiedIMExVal::new(IMExVal::Single(-1), Quantifier::Finite(2),),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![
+                        QuantifiedIMExVal::new(IMExVal::Single(7), Quantifier::Finite(1),),
+                        QuantifiedIMExVal::new(IMExVal::Single(0), Quantifier::Finite(1),)
+                    ])),
+                    Quantifier::Infinite,
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_brackets_fails() {
+        IMEx::try_from("[]").unwrap_err();
+
+        IMEx::try_from("[1").unwrap_err();
+
+        IMEx::try_from("1]").unwrap_err();
+
+        IMEx::try_from("[-]").unwrap_err();
+
+        IMEx::try_from("[1-]").unwrap_err();
+
+        IMEx::try_from("[[1]]").unwrap_err();
+
+        IMEx::try_from("[99999999999999999999999]").unwrap_err();
+    }
+
+    #[test]
+    fn names_give_named_imex() -> Result<()> {
+        let i = IMEx::try_from("a[b_2]{2}([_c]0)*")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(IMExVal::Named("a".to_string()), Quantifier::Finite(1),),
+                QuantifiedIMExVal::new(IMExVal::Named("b_2".to_string()), Quantifier::Finite(2),),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![
+                        QuantifiedIMExVal::new(
+                            IMExVal::Named("_c".to_string()),
+                            Quantifier::Finite(1),
+                        ),
+                        QuantifiedIMExVal::new(IMExVal::Single(0), Quantifier::Finite(1),)
+                    ])),
+                    Quantifier::Infinite,
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_names_fails() {
+        IMEx::try_from("ab_").unwrap_err();
+
+        IMEx::try_from("[2b]").unwrap_err();
+
+        IMEx::try_from("[a-b]").unwrap_err();
+    }
+
+    #[test]
+    fn resolving_names_gives_indexed_imex() -> Result<()> {
+        let i = IMEx::try_from("x(y{2}[zed]3)*")?.resolve_names(&["zed", "y", "x"])?;
+
+        assert_eq!(i, IMEx::try_from("2(1{2}03)*")?);
+        Ok(())
+    }
+
+    #[test]
+    fn resolving_unknown_names_fails() -> Result<()> {
+        IMEx::try_from("a(b)*")?.resolve_names(&["a"]).unwrap_err();
+
+        Ok(())
+    }
+
+    #[test]
+    fn pipes_give_alternation_imex() -> Result<()> {
+        let i = IMEx::try_from("(0|1)*(2{2}3||4)")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(
+                    IMExVal::Alternation(vec![
+                        IMEx::new(vec![QuantifiedIMExVal::new(
+                            IMExVal::Single(0),
+                            Quantifier::Finite(1),
+                        )]),
+                        IMEx::new(vec![QuantifiedIMExVal::new(
+                            IMExVal::Single(1),
+                            Quantifier::Finite(1),
+                        )]),
+                    ]),
+                    Quantifier::Infinite,
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Alternation(vec![
+                        IMEx::new(vec![
+                            QuantifiedIMExVal::new(IMExVal::Single(2), Quantifier::Finite(2),),
+                            QuantifiedIMExVal::new(IMExVal::Single(3), Quantifier::Finite(1),),
+                        ]),
+                        IMEx::new(vec![]),
+                        IMEx::new(vec![QuantifiedIMExVal::new(
+                            IMExVal::Single(4),
+                            Quantifier::Finite(1),
+                        )]),
+                    ]),
+                    Quantifier::Finite(1),
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ungrouped_pipes_fails() {
+        IMEx::try_from("0|1").unwrap_err();
+
+        IMEx::try_from("(0|1)|2").unwrap_err();
+
+        IMEx::try_from("|").unwrap_err();
+    }
+
+    #[test]
+    fn brackets_with_sets_give_class_imex() -> Result<()> {
+        let i = IMEx::try_from("[0,2][1-3]*[5]")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(
+                    IMExVal::Class(SourceClass::new(vec![0, 2])),
+                    Quantifier::Finite(1),
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Class(SourceClass::new(vec![1, 2, 3])),
+                    Quantifier::Infinite,
+                ),
+                QuantifiedIMExVal::new(IMExVal::Single(5), Quantifier::Finite(1)),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn tildes_give_discard_imex() -> Result<()> {
+        let i = IMEx::try_from("~0{2}(1~2)*")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(
+                    IMExVal::Discard(Box::new(IMExVal::Single(0))),
+                    Quantifier::Finite(2),
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![
+                        QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1),),
+                        QuantifiedIMExVal::new(
+                            IMExVal::Discard(Box::new(IMExVal::Single(2))),
+                            Quantifier::Finite(1),
+                        )
+                    ])),
+                    Quantifier::Infinite,
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_tildes_fails() {
+        IMEx::try_from("0~").unwrap_err();
+
+        IMEx::try_from("~*").unwrap_err();
+
+        IMEx::try_from("0{~2}").unwrap_err();
+    }
+
+    #[test]
+    fn dots_give_any_class_imex() -> Result<()> {
+        let i = IMEx::try_from("0{5}.(.)*")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(IMExVal::Single(0), Quantifier::Finite(5)),
+                QuantifiedIMExVal::new(IMExVal::Class(SourceClass::any()), Quantifier::Finite(1)),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![QuantifiedIMExVal::new(
+                        IMExVal::Class(SourceClass::any()),
+                        Quantifier::Finite(1),
+                    )])),
+                    Quantifier::Infinite,
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn question_marks_in_parens_give_conditional_imex() -> Result<()> {
+        let i = IMEx::try_from("(?0:01)*(?!a:1)")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(
+                    IMExVal::Conditional(Conditional::new(
+                        IMExVal::Single(0),
+                        false,
+                        IMEx::new(vec![
+                            QuantifiedIMExVal::new(IMExVal::Single(0), Quantifier::Finite(1),),
+                            QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1),),
+                        ])
+                    )),
+                    Quantifier::Infinite,
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Conditional(Conditional::new(
+                        IMExVal::Named("a".to_string()),
+                        true,
+                        IMEx::new(vec![QuantifiedIMExVal::new(
+                            IMExVal::Single(1),
+                            Quantifier::Finite(1),
+                        )])
+                    )),
+                    Quantifier::Finite(1),
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn resolving_names_resolves_conditional_sources() -> Result<()> {
+        let i = IMEx::try_from("(?!a:b)")?.resolve_names(&["b", "a"])?;
+
+        assert_eq!(i, IMEx::try_from("(?!1:0)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn dollars_give_anchor_imex() -> Result<()> {
+        let i = IMEx::try_from("0*$0([1]$[b])")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(IMExVal::Single(0), Quantifier::Infinite),
+                QuantifiedIMExVal::new(
+                    IMExVal::Anchor(Box::new(IMExVal::Single(0))),
+                    Quantifier::Finite(1)
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::new(vec![
+                        QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1),),
+                        QuantifiedIMExVal::new(
+                            IMExVal::Anchor(Box::new(IMExVal::Named("b".to_string()))),
+                            Quantifier::Finite(1),
+                        )
+                    ])),
+                    Quantifier::Finite(1),
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_anchors_fails() {
+        IMEx::try_from("$").unwrap_err();
+
+        IMEx::try_from("$0*").unwrap_err();
+
+        IMEx::try_from("$0{2}").unwrap_err();
+
+        IMEx::try_from("$(0)").unwrap_err();
+
+        IMEx::try_from("$[0,1]").unwrap_err();
+    }
+
+    #[test]
+    fn named_groups_give_callable_imex() -> Result<()> {
+        let i = IMEx::try_from("(&b)(?<b>0{3}1)(22)(&b)*")?;
+        let b = IMEx::try_from("0{3}1")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(
+                    IMExVal::Call("b".to_string(), b.clone()),
+                    Quantifier::Finite(1),
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::NamedGroup("b".to_string(), b.clone()),
+                    Quantifier::Finite(1),
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::Group(IMEx::try_from("22")?),
+                    Quantifier::Finite(1),
+                ),
+                QuantifiedIMExVal::new(IMExVal::Call("b".to_string(), b), Quantifier::Infinite,),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn nested_calls_give_nested_imex() -> Result<()> {
+        let i = IMEx::try_from("(?<a>0)(?<b>(&a)1)(&b)")?;
+        let a = IMEx::try_from("0")?;
+        let b = IMEx::new(vec![
+            QuantifiedIMExVal::new(
+                IMExVal::Call("a".to_string(), a.clone()),
+                Quantifier::Finite(1),
+            ),
+            QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1)),
+        ]);
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![
+                QuantifiedIMExVal::new(
+                    IMExVal::NamedGroup("a".to_string(), a),
+                    Quantifier::Finite(1),
+                ),
+                QuantifiedIMExVal::new(
+                    IMExVal::NamedGroup("b".to_string(), b.clone()),
+                    Quantifier::Finite(1),
+                ),
+                QuantifiedIMExVal::new(IMExVal::Call("b".to_string(), b), Quantifier::Finite(1),),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_calls_fails() {
+        IMEx::try_from("(&a)").unwrap_err();
+
+        IMEx::try_from("(?<a>0(&a))").unwrap_err();
+
+        IMEx::try_from("(?<a>(&b))(?<b>1(&a))").unwrap_err();
+
+        IMEx::try_from("(?<a>0)(?<a>1)").unwrap_err();
+
+        IMEx::try_from("(?<>0)").unwrap_err();
+
+        IMEx::try_from("(&)").unwrap_err();
+
+        IMEx::try_from("(&a0)(?<a0>1)0(&a0").unwrap_err();
+
+        IMEx::try_from("(?<a>0)(&a)1)").unwrap_err();
+    }
+
+    #[test]
+    fn extended_imex_ignores_whitespace_and_comments() -> Result<()> {
+        let expected = IMEx::try_from("(0{3}[12])*1")?;
+
+        assert_eq!(IMEx::parse_extended(" ( 0 { 3 } [12] ) *\n\t1 ")?, expected);
+        assert_eq!(
+            IMEx::parse_extended("# header\n(0{3} # zeroes\n [12])* # repeat\n1 #")?,
+            expected
+        );
+        assert_eq!(
+            IMEx::try_from("(?x)\n  (0{3}[12])*  # repeat\n  1\n")?,
+            expected
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_extended_imex_fails() {
+        IMEx::try_from("(?x) 0 (").unwrap_err();
+
+        IMEx::try_from(" (?x) 0").unwrap_err();
+
+        IMEx::try_from("0 1").unwrap_err();
+
+        IMEx::parse_extended("0 # 1 )\n)").unwrap_err();
+    }
+
+    #[test]
+    fn extended_imex_whitespace_separates_tokens() -> Result<()> {
+        let error = IMEx::parse_extended("[1 2]").unwrap_err();
+        assert_eq!(error.kind(), &ParseErrorKind::UnexpectedChar(' '));
+        assert_eq!(error.span(), 2..3);
+
+        let error = IMEx::parse_extended("0{1 0}").unwrap_err();
+        assert_eq!(error.kind(), &ParseErrorKind::BadQuantifier);
+        assert_eq!(error.span(), 1..6);
+
+        let error = IMEx::parse_extended("(?<a b>0)").unwrap_err();
+        assert_eq!(error.kind(), &ParseErrorKind::UnexpectedChar(' '));
+        assert_eq!(error.span(), 4..5);
+
+        IMEx::parse_extended("[1 # one\n2]").unwrap_err();
+        IMEx::parse_extended("(0%7 0|1%30)").unwrap_err();
+        IMEx::parse_extended("[- 1]").unwrap_err();
+
+        assert_eq!(
+            IMEx::parse_extended("0 1 [ 1 , -1 ] { 2 , } (?<ab> 0 ) ( & ab ) ( 0 % 70 | 1 % 30 )")?,
+            IMEx::try_from("01[1,-1]{2,}(?<ab>0)(&ab)(0%70|1%30)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn params_give_param_quantifiers() -> Result<()> {
+        let i = IMEx::try_from("(0{$burst}1)*")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![QuantifiedIMExVal::new(
+                IMExVal::Group(IMEx::new(vec![
+                    QuantifiedIMExVal::new(
+                        IMExVal::Single(0),
+                        Quantifier::Param("burst".to_string()),
+                    ),
+                    QuantifiedIMExVal::new(IMExVal::Single(1), Quantifier::Finite(1),),
+                ])),
+                Quantifier::Infinite,
+            )])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bound_params_give_finite_quantifiers() -> Result<()> {
+        let mut params = HashMap::new();
+        params.insert("a".to_string(), 2);
+        params.insert("b_2".to_string(), 0);
+
+        assert_eq!(
+            IMEx::try_from("(?<g>1{$a})(0{$b_2}(&g)|~2{$a})*")?.bind_params(&params)?,
+            IMEx::try_from("(?<g>1{2})(0{0}(&g)|~2{2})*")?
+        );
+        IMEx::try_from("(0{$a}1{$c})*")?
+            .bind_params(&params)
+            .unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn bad_params_fails() {
+        IMEx::try_from("0{$}").unwrap_err();
+
+        IMEx::try_from("0{$1}").unwrap_err();
+
+        IMEx::try_from("0{$a,2}").unwrap_err();
+
+        IMEx::try_from("0{$a").unwrap_err();
+    }
+
+    #[test]
+    fn weights_give_weighted_choice() -> Result<()> {
+        let i = IMEx::try_from("(0%70|1(2)%30)*")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![QuantifiedIMExVal::new(
+                IMExVal::Weighted(WeightedChoice::new(vec![
+                    (IMEx::try_from("0")?, 70),
+                    (IMEx::try_from("1(2)")?, 30),
+                ])),
+                Quantifier::Infinite,
+            )])
+        );
+        IMEx::try_from("(0%70|1)*").unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn angle_brackets_give_sorted_merge() -> Result<()> {
+        let i = IMEx::try_from("<0[12]a>*")?;
+
+        assert_eq!(
+            i,
+            IMEx::new(vec![QuantifiedIMExVal::new(
+                IMExVal::Sorted(SortedMerge::new(vec![
+                    IMExVal::Single(0),
+                    IMExVal::Single(12),
+                    IMExVal::Named("a".to_string()),
+                ])),
+                Quantifier::Infinite,
+            )])
+        );
+        IMEx::try_from("<0").unwrap_err();
+        IMEx::try_from("<>").unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn bad_chars_fails() {
+        IMEx::try_from("^[0]+$").unwrap_err();
+    }
+
+    #[test]
+    fn too_many_closed_parens_fails() {
+        IMEx::try_from("0(1)2)3(4)5").unwrap_err();
+
+        IMEx::try_from("0{1}2}3{4}5").unwrap_err();
+
+        IMEx::try_from("0(1)2(3))").unwrap_err();
+    }
+
+    #[test]
+    fn too_many_open_parens_fails() {
+        IMEx::try_from("0(1)2(3(4)5").unwrap_err();
+
+        IMEx::try_from("0{1}2{3{4}5").unwrap_err();
+
+        IMEx::try_from("0{1}23{4}5{6").unwrap_err();
+
+        IMEx::try_from("((0)1(2)3").unwrap_err();
+    }
+
+    #[test]
+    fn mismatched_parens_fails() {
+        IMEx::try_from(")(").unwrap_err();
+
+        IMEx::try_from("(3{)}").unwrap_err();
+    }
+
+    #[test]
+    fn bad_repeat_targets_fails() {
+        IMEx::try_from("(*4)").unwrap_err();
+
+        IMEx::try_from("({6}6)").unwrap_err();
+
+        IMEx::try_from("*2").unwrap_err();
+
+        IMEx::try_from("{4}4").unwrap_err();
+
+        IMEx::try_from("5{*5}").unwrap_err();
+    }
+
+    #[test]
+    fn bad_repeat_bracket_contents_fails() {
+        IMEx::try_from("5{5*}").unwrap_err();
+
+        IMEx::try_from("6{(6)}").unwrap_err();
+
+        IMEx::try_from("7{7{7}}").unwrap_err();
+    }
+
+    fn error_of(imex_str: &str) -> (ParseErrorKind, std::ops::Range<usize>) {
+        let error = IMEx::try_from(imex_str).unwrap_err();
+        (error.kind().clone(), error.span())
+    }
+
+    #[test]
+    fn unbalanced_parens_are_reported() {
+        assert_eq!(error_of("(01"), (ParseErrorKind::UnbalancedParen, 0..1));
+        assert_eq!(error_of(")("), (ParseErrorKind::UnbalancedParen, 0..1));
+        assert_eq!(error_of("0(1)2)3"), (ParseErrorKind::UnbalancedParen, 5..6));
+        assert_eq!(error_of("<01"), (ParseErrorKind::UnbalancedParen, 0..1));
+        assert_eq!(error_of("0[1,2"), (ParseErrorKind::UnbalancedParen, 1..2));
+    }
+
+    #[test]
+    fn bad_quantifiers_are_reported() {
+        assert_eq!(error_of("(3{)}"), (ParseErrorKind::BadQuantifier, 2..5));
+        assert_eq!(error_of("0{3,1}"), (ParseErrorKind::BadQuantifier, 1..6));
+        assert_eq!(error_of("0{3"), (ParseErrorKind::BadQuantifier, 1..3));
+        assert_eq!(error_of("*2"), (ParseErrorKind::BadQuantifier, 0..1));
+        assert_eq!(error_of("0**"), (ParseErrorKind::BadQuantifier, 2..3));
+    }
+
+    #[test]
+    fn too_large_numbers_are_reported() {
+        let count = "0{99999999999999999999}";
+        assert_eq!(error_of(count), (ParseErrorKind::Overflow, 2..22));
+        let range = "0{1,99999999999999999999}";
+        assert_eq!(error_of(range), (ParseErrorKind::Overflow, 4..24));
+        let index = "[99999999999999999999]";
+        assert_eq!(error_of(index), (ParseErrorKind::Overflow, 1..21));
+        let class = "[0,-99999999999999999999]";
+        assert_eq!(error_of(class), (ParseErrorKind::Overflow, 3..24));
+        let weight = "(0%99999999999999999999|1%1)";
+        assert_eq!(error_of(weight), (ParseErrorKind::Overflow, 3..23));
+    }
+
+    #[test]
+    fn too_large_classes_are_reported() -> Result<()> {
+        assert_eq!(
+            error_of("0[0-9999999999]"),
+            (ParseErrorKind::Overflow, 2..14)
+        );
+        assert_eq!(error_of("[1,0-65535]*"), (ParseErrorKind::Overflow, 0..11));
+        let widest = IMEx::try_from("[0-65535]")?;
+        assert_eq!(
+            widest.vals[0].get_val(),
+            &IMExVal::Class(SourceClass::new((0..=65535).collect()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unexpected_chars_and_ends_are_reported() {
+        assert_eq!(error_of("0@0"), (ParseErrorKind::UnexpectedChar('@'), 1..2));
+        assert_eq!(error_of("0~"), (ParseErrorKind::UnexpectedEnd, 2..2));
+
+        let error = IMEx::try_from("0~").unwrap_err();
+        assert!(error.expected().contains(&"'('".to_string()));
+        assert!(error.expected().contains(&"a digit".to_string()));
+    }
+
+    #[test]
+    fn bad_calls_are_reported() {
+        assert_eq!(
+            error_of("0(&a)"),
+            (ParseErrorKind::UndefinedGroup("a".to_string()), 1..5)
+        );
+        assert_eq!(
+            error_of("(?<a>0(&a))"),
+            (ParseErrorKind::RecursiveGroup("a".to_string()), 6..10)
+        );
+        assert_eq!(
+            error_of("(?<a>0)(?<a>1)"),
+            (ParseErrorKind::DuplicateGroup("a".to_string()), 7..12)
+        );
+        assert_eq!(
+            error_of("(?x)(?<a>0)\n  (& b)"),
+            (ParseErrorKind::UndefinedGroup("b".to_string()), 14..19)
+        );
+    }
+
+    #[test]
+    fn extended_imex_errors_point_into_original_string() {
+        let imex = "(?x)\n  0 # zero\n  1{3,1}\n";
+        let error = IMEx::try_from(imex).unwrap_err();
+
+        assert_eq!(error.kind(), &ParseErrorKind::BadQuantifier);
+        assert_eq!(&imex[error.span()], "{3,1}");
+        assert_eq!(
+            error.render(imex),
+            "  1{3,1}\n   ^^^^^\nbad quantifier at 19..24, expected '}'"
+        );
+
+        let error = IMEx::parse_extended("(0 # 1 )\n").unwrap_err();
+        assert_eq!(error.span(), 0..1);
+    }
+
+    #[test]
+    fn printed_quantifier_reparses() {
+        let mut numbers = Numbers::new(18);
+        for _ in 0..1000 {
+            let quantifier = random::quantifier(&mut numbers);
+            let printed = quantifier.to_string();
+
+            assert_eq!(
+                Quantifier::parse(&printed),
+                Ok(("", quantifier)),
+                "{}",
+                printed
+            );
+        }
+    }
+
+    #[test]
+    fn printed_imex_val_reparses() {
+        let mut numbers = Numbers::new(18);
+        for _ in 0..1000 {
+            let val = random::val(&mut numbers, 3);
+            let printed = val.to_string();
+
+            assert_eq!(IMExVal::parse(&printed), Ok(("", val)), "{}", printed);
+        }
+    }
+
+    #[test]
+    fn printed_qimex_val_reparses() {
+        let mut numbers = Numbers::new(18);
+        for _ in 0..1000 {
+            let qimex_val = random::qimex_val(&mut numbers, 3);
+            let printed = qimex_val.to_string();
+
+            assert_eq!(
+                QuantifiedIMExVal::parse(&printed),
+                Ok(("", qimex_val)),
+                "{}",
+                printed
+            );
+        }
+    }
+
+    #[test]
+    fn printed_imex_reparses() -> Result<()> {
+        let mut numbers = Numbers::new(18);
+        for _ in 0..1000 {
+            let imex = random::imex(&mut numbers, 4);
+            let printed = imex.to_string();
+
+            assert_eq!(IMEx::try_from(printed.as_str())?, imex, "{}", printed);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn printed_imex_with_calls_reparses() -> Result<()> {
+        for written in &[
+            "(?<a>01)(&a)*",
+            "(?<a>0)(?<b>(&a)1)(&b){2}",
+            "(?<pair>01)((&pair)|2)",
+        ] {
+            let imex = IMEx::try_from(*written)?;
+
+            assert_eq!(imex.to_string(), *written);
+            assert_eq!(IMEx::try_from(imex.to_string().as_str())?, imex);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,490 @@
+use crate::{
+    parse_signed_number, parse_weighted_tail, repeated_then, split_marks, unmarked, Conditional,
+    Failure, IMEx, Mark, Marked, ParseErrorKind, ParserCombinator, Resolved, SortedMerge,
+    SourceClass, WeightedChoice, WeightedTail,
+};
+use nom::{
+    branch::alt,
+    bytes::complete::take_while,
+    character::complete::{anychar, char, one_of},
+    combinator::{map, recognize, verify},
+    sequence::{delimited, pair, preceded},
+    IResult,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{Error, ErrorKind::InvalidInput, Result},
+};
+
+/// [`IMEx`]: ./struct.IMEx.html
+/// Represents a quantifiable value in a parsed [`IMEx`]. So, this is either a Single, which
+/// contains an index for indexing iterators, or a Group, which contains an inner parsed [`IMEx`].
+///
+/// Negative indices in a Single count backwards from the last iterator, so `-1` refers to the
+/// last one.
+///
+/// A Named value refers to an iterator by name instead of by index. Names need to be resolved
+/// into Singles with [`IMEx::resolve_names`](./struct.IMEx.html#method.resolve_names) before they
+/// can be iterated, otherwise they behave like an out-of-range index.
+///
+/// An Alternation is a group of alternative [`IMEx`]es, separated by `|`. The first alternative
+/// that gives an item is used, and the rest are discarded.
+///
+/// A Weighted value contains a [`WeightedChoice`](./struct.WeightedChoice.html), written like
+/// `(0%70|1%30)`, which picks one of its alternatives at random, by weight.
+///
+/// A Class contains a [`SourceClass`](./struct.SourceClass.html), which gives an item from the
+/// first iterator in a set that isn't exhausted. The wildcard `.` is a Class of every iterator.
+///
+/// A Sorted value contains a [`SortedMerge`](./struct.SortedMerge.html), written like `<012>`,
+/// which gives the smallest next item of the iterators it lists.
+///
+/// A Discard, written with a leading `~`, takes items from the iterators like its inner value
+/// would, but discards them instead of including them in the merge.
+///
+/// A Conditional contains a [`Conditional`](./struct.Conditional.html) group, which is only used
+/// depending on whether an iterator is exhausted.
+///
+/// An Anchor, written like `$0`, is a zero-width value that checks whether the iterator referred
+/// to by its inner Single or Named value is exhausted. It never takes any items, and if the
+/// iterator isn't exhausted, the rest of the enclosing [`IMEx`] is skipped.
+///
+/// A NamedGroup, written like `(?<name>01)`, is a Group that can be called elsewhere in the same
+/// [`IMEx`] with a Call, written like `(&name)`. When an [`IMEx`] is parsed, each Call is given a
+/// copy of the [`IMEx`] of the NamedGroup it calls, and is iterated like a Group.
+#[derive(PartialEq, Debug, Clone)]
+pub enum IMExVal {
+    Single(isize),
+    Named(String),
+    Group(IMEx),
+    NamedGroup(String, IMEx),
+    Call(String, IMEx),
+    Alternation(Vec<IMEx>),
+    Weighted(WeightedChoice),
+    Class(SourceClass),
+    Sorted(SortedMerge),
+    Discard(Box<IMExVal>),
+    Conditional(Conditional),
+    Anchor(Box<IMExVal>),
+}
+
+impl IMExVal {
+    /// Returns the index of the iterator that a Single refers to, or None for other values.
+    pub fn source_index(&self) -> Option<isize> {
+        match self {
+            IMExVal::Single(index) => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// Returns the names that this value refers to iterators by, in order.
+    pub(crate) fn names(&self) -> Vec<&str> {
+        match self {
+            IMExVal::Named(name) => vec![name.as_str()],
+            IMExVal::Anchor(val) | IMExVal::Discard(val) => val.names(),
+            IMExVal::Sorted(sorted) => sorted
+                .get_sources()
+                .iter()
+                .flat_map(IMExVal::names)
+                .collect(),
+            IMExVal::Conditional(conditional) => {
+                let mut names = conditional.get_source().names();
+                names.extend(conditional.get_imex().names());
+                names
+            }
+            val => val
+                .inner_imexes()
+                .into_iter()
+                .flat_map(IMEx::names)
+                .collect(),
+        }
+    }
+
+    /// Returns true if this value is or contains a sorted merge, like `<01>`.
+    pub(crate) fn has_sorted(&self) -> bool {
+        match self {
+            IMExVal::Sorted(_) => true,
+            IMExVal::Anchor(val) | IMExVal::Discard(val) => val.has_sorted(),
+            val => val.inner_imexes().into_iter().any(IMEx::has_sorted),
+        }
+    }
+
+    /// Returns the IMExes directly within this value, like the IMEx of a Group or the
+    /// alternatives of an Alternation.
+    pub(crate) fn inner_imexes(&self) -> Vec<&IMEx> {
+        match self {
+            IMExVal::Group(imex) | IMExVal::NamedGroup(_, imex) | IMExVal::Call(_, imex) => {
+                vec![imex]
+            }
+            IMExVal::Alternation(imexes) => imexes.iter().collect(),
+            IMExVal::Weighted(choice) => choice
+                .get_alternatives()
+                .iter()
+                .map(|(imex, _)| imex)
+                .collect(),
+            IMExVal::Conditional(conditional) => vec![conditional.get_imex()],
+            IMExVal::Discard(val) => val.inner_imexes(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns true if this value never takes items from the iterators, like an Anchor.
+    pub fn is_zero_width(&self) -> bool {
+        matches!(self, IMExVal::Anchor(_))
+    }
+
+    /// Replaces Named values with Singles indexing the position of their name in `names`.
+    ///
+    /// # Error
+    /// Results in an error if a name is not in `names`.
+    pub(crate) fn resolve_names<S: AsRef<str>>(self, names: &[S]) -> Result<IMExVal> {
+        match self {
+            IMExVal::Named(name) => match names.iter().position(|n| n.as_ref() == name) {
+                Some(index) => Ok(IMExVal::Single(index as isize)),
+                None => Err(Error::new(
+                    InvalidInput,
+                    format!("IMEx refers to unknown iterator name \"{}\"", name),
+                )),
+            },
+            IMExVal::Group(imex) => Ok(IMExVal::Group(imex.resolve_names(names)?)),
+            IMExVal::NamedGroup(name, imex) => {
+                Ok(IMExVal::NamedGroup(name, imex.resolve_names(names)?))
+            }
+            IMExVal::Call(name, imex) => Ok(IMExVal::Call(name, imex.resolve_names(names)?)),
+            IMExVal::Discard(val) => Ok(IMExVal::Discard(Box::new(val.resolve_names(names)?))),
+            IMExVal::Conditional(conditional) => {
+                Ok(IMExVal::Conditional(conditional.resolve_names(names)?))
+            }
+            IMExVal::Anchor(val) => Ok(IMExVal::Anchor(Box::new(val.resolve_names(names)?))),
+            IMExVal::Alternation(imexes) => Ok(IMExVal::Alternation(
+                imexes
+                    .into_iter()
+                    .map(|imex| imex.resolve_names(names))
+                    .collect::<Result<Vec<IMEx>>>()?,
+            )),
+            IMExVal::Weighted(choice) => Ok(IMExVal::Weighted(choice.resolve_names(names)?)),
+            IMExVal::Sorted(sorted) => Ok(IMExVal::Sorted(sorted.resolve_names(names)?)),
+            single => Ok(single),
+        }
+    }
+
+    /// Binds the parameters within this value to their counts in `params`.
+    ///
+    /// # Error
+    /// Results in an error if a parameter is not in `params`.
+    pub(crate) fn bind_params(self, params: &HashMap<String, usize>) -> Result<IMExVal> {
+        match self {
+            IMExVal::Group(imex) => Ok(IMExVal::Group(imex.bind_params(params)?)),
+            IMExVal::NamedGroup(name, imex) => {
+                Ok(IMExVal::NamedGroup(name, imex.bind_params(params)?))
+            }
+            IMExVal::Call(name, imex) => Ok(IMExVal::Call(name, imex.bind_params(params)?)),
+            IMExVal::Discard(val) => Ok(IMExVal::Discard(Box::new(val.bind_params(params)?))),
+            IMExVal::Conditional(conditional) => {
+                Ok(IMExVal::Conditional(conditional.bind_params(params)?))
+            }
+            IMExVal::Alternation(imexes) => Ok(IMExVal::Alternation(
+                imexes
+                    .into_iter()
+                    .map(|imex| imex.bind_params(params))
+                    .collect::<Result<Vec<IMEx>>>()?,
+            )),
+            IMExVal::Weighted(choice) => Ok(IMExVal::Weighted(choice.bind_params(params)?)),
+            val => Ok(val),
+        }
+    }
+
+    /// Adds the NamedGroups within this value to `definitions`.
+    ///
+    /// # Error
+    /// Results in an error if a NamedGroup's name is already in `definitions`.
+    pub(crate) fn collect_definitions(
+        &self,
+        definitions: &mut HashMap<String, IMEx>,
+    ) -> Resolved<()> {
+        match self {
+            IMExVal::NamedGroup(name, imex) => {
+                if definitions.insert(name.clone(), imex.clone()).is_some() {
+                    return Err(ParseErrorKind::DuplicateGroup(name.clone()));
+                }
+                imex.collect_definitions(definitions)
+            }
+            IMExVal::Group(imex) => imex.collect_definitions(definitions),
+            IMExVal::Alternation(imexes) => imexes
+                .iter()
+                .try_for_each(|imex| imex.collect_definitions(definitions)),
+            IMExVal::Weighted(choice) => choice
+                .get_alternatives()
+                .iter()
+                .try_for_each(|(imex, _)| imex.collect_definitions(definitions)),
+            IMExVal::Discard(val) => val.collect_definitions(definitions),
+            IMExVal::Conditional(conditional) => {
+                conditional.get_imex().collect_definitions(definitions)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Gives each Call within this value a copy of the IMEx it calls from `definitions`.
+    /// `callers` holds the names of the NamedGroups that this value is within.
+    ///
+    /// # Error
+    /// Results in an error if a Call's name isn't in `definitions`, or if it's in `callers`,
+    /// which would make it recursive.
+    pub(crate) fn resolve_calls(
+        self,
+        definitions: &HashMap<String, IMEx>,
+        callers: &mut Vec<String>,
+    ) -> Resolved<IMExVal> {
+        match self {
+            IMExVal::NamedGroup(name, imex) => {
+                callers.push(name);
+                let imex = imex.resolve_calls(definitions, callers)?;
+                let name = callers.pop().expect("Expected caller to be pushed");
+                Ok(IMExVal::NamedGroup(name, imex))
+            }
+            IMExVal::Call(name, _) => {
+                if callers.contains(&name) {
+                    return Err(ParseErrorKind::RecursiveGroup(name));
+                }
+                let imex = match definitions.get(&name) {
+                    Some(imex) => imex.clone(),
+                    None => return Err(ParseErrorKind::UndefinedGroup(name)),
+                };
+                callers.push(name);
+                let imex = imex.resolve_calls(definitions, callers)?;
+                let name = callers.pop().expect("Expected caller to be pushed");
+                Ok(IMExVal::Call(name, imex))
+            }
+            IMExVal::Group(imex) => Ok(IMExVal::Group(imex.resolve_calls(definitions, callers)?)),
+            IMExVal::Alternation(imexes) => Ok(IMExVal::Alternation(
+                imexes
+                    .into_iter()
+                    .map(|imex| imex.resolve_calls(definitions, callers))
+                    .collect::<Resolved<Vec<IMEx>>>()?,
+            )),
+            IMExVal::Weighted(choice) => Ok(IMExVal::Weighted(
+                choice.resolve_calls(definitions, callers)?,
+            )),
+            IMExVal::Discard(val) => Ok(IMExVal::Discard(Box::new(
+                val.resolve_calls(definitions, callers)?,
+            ))),
+            IMExVal::Conditional(conditional) => Ok(IMExVal::Conditional(
+                conditional.resolve_calls(definitions, callers)?,
+            )),
+            val => Ok(val),
+        }
+    }
+}
+
+fn parse_digit_index(input: &str) -> IResult<&str, isize, Failure> {
+    let (input, x) = one_of("0123456789")(input)?;
+    Ok((input, (x as u8 - b'0') as isize))
+}
+
+fn parse_bracketed_index(input: &str) -> IResult<&str, isize, Failure> {
+    delimited(char('['), parse_signed_number, char(']'))(input)
+}
+
+pub(crate) fn parse_identifier(input: &str) -> IResult<&str, &str, Failure> {
+    recognize(pair(
+        verify(anychar, |c: &char| c.is_ascii_alphabetic() || *c == '_'),
+        take_while(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+    ))(input)
+}
+
+fn parse_single_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (rest, index) = alt((parse_digit_index, parse_bracketed_index))(input)?;
+    let marked = Marked::new(Mark::Indices(index..=index), input, rest);
+    Ok((rest, (IMExVal::Single(index), vec![marked])))
+}
+
+fn parse_named_imex_val(input: &str) -> IResult<&str, IMExVal, Failure> {
+    let (input, name) = alt((
+        recognize(verify(anychar, |c: &char| c.is_ascii_alphabetic())),
+        delimited(char('['), parse_identifier, char(']')),
+    ))(input)?;
+    Ok((input, IMExVal::Named(name.to_string())))
+}
+
+/// Parses a reference to a single iterator, either by index or by name.
+pub(crate) fn parse_source_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    alt((parse_single_imex_val, unmarked(parse_named_imex_val)))(input)
+}
+
+fn parse_class_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (input, (class, marks)) = SourceClass::parse_marked(input)?;
+    Ok((input, (IMExVal::Class(class), marks)))
+}
+
+fn parse_discard_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (input, (val, marks)) = preceded(char('~'), IMExVal::parse_marked)(input)?;
+    Ok((input, (IMExVal::Discard(Box::new(val)), marks)))
+}
+
+fn parse_conditional_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (input, (conditional, marks)) = Conditional::parse_marked(input)?;
+    Ok((input, (IMExVal::Conditional(conditional), marks)))
+}
+
+fn parse_anchor_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (input, (val, marks)) = preceded(char('$'), parse_source_imex_val)(input)?;
+    Ok((input, (IMExVal::Anchor(Box::new(val)), marks)))
+}
+
+fn parse_named_group_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (rest, name) = preceded(
+        char('('),
+        preceded(char('?'), delimited(char('<'), parse_identifier, char('>'))),
+    )(input)?;
+    let definition = Marked::new(Mark::Definition(name.to_string()), input, rest);
+    let (rest, (imex, marks)) = IMEx::parse_marked(rest)?;
+    let (rest, _) = char(')')(rest)?;
+    let marks = std::iter::once(definition).chain(marks).collect();
+    Ok((rest, (IMExVal::NamedGroup(name.to_string(), imex), marks)))
+}
+
+fn parse_call_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (rest, name) =
+        delimited(char('('), preceded(char('&'), parse_identifier), char(')'))(input)?;
+    let call = Marked::new(Mark::Call(name.to_string()), input, rest);
+    Ok((
+        rest,
+        (
+            IMExVal::Call(name.to_string(), IMEx::new(vec![])),
+            vec![call],
+        ),
+    ))
+}
+
+fn parse_sorted_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (input, (sorted, marks)) = SortedMerge::parse_marked(input)?;
+    Ok((input, (IMExVal::Sorted(sorted), marks)))
+}
+
+/// What follows the first IMEx in parentheses, which tells whether they're a group, an
+/// alternation or a weighted choice.
+enum GroupTail {
+    Weighted(WeightedTail),
+    Alternatives(Vec<(IMEx, Vec<Marked>)>),
+}
+
+fn parse_group_imex_val(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+    let (input, _) = char('(')(input)?;
+    let (input, first) = IMEx::parse_marked(input)?;
+    let (input, tail) = alt((
+        map(parse_weighted_tail, GroupTail::Weighted),
+        map(
+            repeated_then(preceded(char('|'), IMEx::parse_marked), char(')')),
+            GroupTail::Alternatives,
+        ),
+    ))(input)?;
+    let mut alternatives = match tail {
+        GroupTail::Weighted(tail) => {
+            let (choice, marks) = WeightedChoice::from_tail(first, tail);
+            return Ok((input, (IMExVal::Weighted(choice), marks)));
+        }
+        GroupTail::Alternatives(alternatives) => alternatives,
+    };
+    alternatives.insert(0, first);
+    let (mut alternatives, marks) = split_marks(alternatives);
+    if alternatives.len() == 1 {
+        Ok((input, (IMExVal::Group(alternatives.remove(0)), marks)))
+    } else {
+        Ok((input, (IMExVal::Alternation(alternatives), marks)))
+    }
+}
+
+/// Prints the value as it is written in an IMEx. Indices past 9 and names longer than a letter
+/// are printed in brackets, and a Call is printed without the IMEx of the group it calls.
+impl fmt::Display for IMExVal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IMExVal::Single(index @ 0..=9) => write!(f, "{}", index),
+            IMExVal::Single(index) => write!(f, "[{}]", index),
+            IMExVal::Named(name)
+                if name.len() == 1 && name.starts_with(|c: char| c.is_ascii_alphabetic()) =>
+            {
+                write!(f, "{}", name)
+            }
+            IMExVal::Named(name) => write!(f, "[{}]", name),
+            IMExVal::Group(imex) => write!(f, "({})", imex),
+            IMExVal::NamedGroup(name, imex) => write!(f, "(?<{}>{})", name, imex),
+            IMExVal::Call(name, _) => write!(f, "(&{})", name),
+            IMExVal::Alternation(imexes) => {
+                write!(f, "(")?;
+                for (position, imex) in imexes.iter().enumerate() {
+                    if position > 0 {
+                        write!(f, "|")?;
+                    }
+                    write!(f, "{}", imex)?;
+                }
+                write!(f, ")")
+            }
+            IMExVal::Weighted(choice) => write!(f, "{}", choice),
+            IMExVal::Class(class) => write!(f, "{}", class),
+            IMExVal::Sorted(sorted) => write!(f, "{}", sorted),
+            IMExVal::Discard(val) => write!(f, "~{}", val),
+            IMExVal::Conditional(conditional) => write!(f, "{}", conditional),
+            IMExVal::Anchor(val) => write!(f, "${}", val),
+        }
+    }
+}
+
+impl ParserCombinator for IMExVal {
+    fn parse_marked(input: &str) -> IResult<&str, (IMExVal, Vec<Marked>), Failure> {
+        alt((
+            parse_source_imex_val,
+            parse_class_imex_val,
+            parse_sorted_imex_val,
+            parse_discard_imex_val,
+            parse_anchor_imex_val,
+            parse_conditional_imex_val,
+            parse_named_group_imex_val,
+            parse_call_imex_val,
+            parse_group_imex_val,
+        ))(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::IMEx;
+    use super::*;
+    use std::{convert::TryFrom, io::Result};
+
+    #[test]
+    fn resolving_unknown_name_fails() {
+        IMExVal::Named("c".to_string())
+            .resolve_names(&["a", "b"])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn imex_val_prints_as_written() -> Result<()> {
+        assert_eq!(IMExVal::Single(7).to_string(), "7");
+        assert_eq!(IMExVal::Single(12).to_string(), "[12]");
+        assert_eq!(IMExVal::Single(-1).to_string(), "[-1]");
+        assert_eq!(IMExVal::Named("a".to_string()).to_string(), "a");
+        assert_eq!(IMExVal::Named("_".to_string()).to_string(), "[_]");
+        assert_eq!(IMExVal::Named("bee".to_string()).to_string(), "[bee]");
+        assert_eq!(
+            IMExVal::Alternation(vec![IMEx::try_from("01")?, IMEx::try_from("")?]).to_string(),
+            "(01|)"
+        );
+        assert_eq!(
+            IMExVal::Discard(Box::new(IMExVal::Anchor(Box::new(IMExVal::Single(0))))).to_string(),
+            "~$0"
+        );
+        assert_eq!(
+            IMExVal::NamedGroup("pair".to_string(), IMEx::try_from("01")?).to_string(),
+            "(?<pair>01)"
+        );
+        assert_eq!(
+            IMExVal::Call("pair".to_string(), IMEx::try_from("01")?).to_string(),
+            "(&pair)"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+//! The IMEx, or Iterator-Merging-Expression, and its parser. This crate is shared by `imex`,
+//! which merges iterators with an IMEx, and `imex-macros`, which parses one at compile time, and
+//! should be used through [`imex::expression`](https://docs.rs/imex/*/imex/expression/index.html).
+pub mod analysis;
+pub mod builder;
+mod conditional;
+mod imex;
+mod imex_val;
+mod parse_error;
+mod quantified_imex_val;
+mod quantifier;
+#[cfg(any(test, feature = "random"))]
+#[doc(hidden)]
+pub mod random;
+mod simplify;
+mod sorted_merge;
+mod source_class;
+mod utils;
+mod weighted_choice;
+
+pub(crate) use self::parse_error::Resolved;
+pub use self::{
+    conditional::Conditional,
+    imex::{IMEx, IndexSpans},
+    imex_val::IMExVal,
+    parse_error::{ParseError, ParseErrorKind},
+    quantified_imex_val::QuantifiedIMExVal,
+    quantifier::Quantifier,
+    sorted_merge::SortedMerge,
+    source_class::SourceClass,
+    weighted_choice::WeightedChoice,
+};
+use imex_val::{parse_identifier, parse_source_imex_val};
+use source_class::MAX_CLASS_SIZE;
+use utils::{
+    parse_number, parse_signed_number, repeated, repeated_then, split_marks, unmarked, Failure,
+    Mark, Marked, ParserCombinator,
+};
+use weighted_choice::{parse_weighted_tail, WeightedTail};
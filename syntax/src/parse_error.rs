@@ -0,0 +1,272 @@
+use crate::utils::{Expected, Failure};
+use std::{error, fmt, io, ops::Range};
+
+/// The reason that an [`IMEx`](./struct.IMEx.html) failed to parse.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ParseErrorKind {
+    /// A group, class, sorted merge or quantifier isn't closed, or is closed without being opened.
+    UnbalancedParen,
+    /// A quantifier is malformed, like `{3,1}`, or isn't preceded by a value, like `*0`.
+    BadQuantifier,
+    /// A number is too large to be an index, count or weight, or a class has too many indices,
+    /// like `[0-9999999999]`.
+    Overflow,
+    /// A character can't be parsed where it appears.
+    UnexpectedChar(char),
+    /// The IMEx ends before a value is finished.
+    UnexpectedEnd,
+    /// A named group is defined more than once.
+    DuplicateGroup(String),
+    /// A call refers to a named group that isn't defined.
+    UndefinedGroup(String),
+    /// A named group calls itself, directly or through other groups.
+    RecursiveGroup(String),
+}
+
+/// The result of resolving the calls to named groups in a parsed IMEx, which fails without a
+/// span. [`IMEx::try_from`](./struct.IMEx.html#impl-TryFrom%3C%26str%3E) finds the span.
+pub(crate) type Resolved<T> = Result<T, ParseErrorKind>;
+
+/// An error from parsing an [`IMEx`](./struct.IMEx.html). Holds what went wrong, the span of
+/// bytes in the IMEx where it went wrong, and the tokens that were expected there instead.
+///
+/// # Example
+/// ```
+/// use imex_syntax::{IMEx, ParseErrorKind};
+/// use std::convert::TryFrom;
+///
+/// let error = IMEx::try_from("[0,1").unwrap_err();
+///
+/// assert_eq!(error.kind(), &ParseErrorKind::UnbalancedParen);
+/// assert_eq!(error.span(), 0..1);
+/// assert_eq!(error.expected(), ["','", "']'"]);
+/// assert_eq!(
+///     error.render("[0,1"),
+///     "[0,1\n^\nunbalanced parenthesis at 0..1, expected ',' or ']'"
+/// );
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    span: Range<usize>,
+    expected: Vec<String>,
+}
+
+impl ParseError {
+    /// Create a new ParseError.
+    pub fn new(kind: ParseErrorKind, span: Range<usize>, expected: Vec<String>) -> ParseError {
+        ParseError {
+            kind,
+            span,
+            expected,
+        }
+    }
+
+    /// Describes the error from the parser combinators failing to parse `imex_str`.
+    pub(crate) fn from_failure(imex_str: &str, failure: Option<Failure>) -> ParseError {
+        let failure = failure.unwrap_or(Failure {
+            remaining: 0,
+            expected: vec![],
+            overflow: None,
+        });
+        let position = imex_str.len() - failure.remaining;
+        if let Some(length) = failure.overflow {
+            return ParseError::new(
+                ParseErrorKind::Overflow,
+                position..position + length,
+                vec![],
+            );
+        }
+
+        let expected = failure
+            .expected
+            .iter()
+            .map(|expected| match expected {
+                Expected::Char(c) => format!("'{}'", c),
+                Expected::Digit => "a digit".to_string(),
+                Expected::End => "end of input".to_string(),
+            })
+            .collect::<Vec<String>>();
+        let (kind, span) = classify(imex_str, position, &failure.expected);
+        ParseError::new(kind, span, expected)
+    }
+
+    /// Returns what went wrong.
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    /// Returns the span of bytes in the IMEx where it went wrong.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Returns the tokens that were expected where it went wrong, like `')'` or `a digit`.
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+
+    /// Moves the span by `f`, for IMExes that were changed before parsing.
+    pub(crate) fn map_span<F: Fn(usize) -> usize>(self, f: F) -> ParseError {
+        let span = f(self.span.start)..f(self.span.end).max(f(self.span.start));
+        ParseError { span, ..self }
+    }
+
+    /// Renders the error as a diagnostic for `imex`, the IMEx that failed to parse. The line of
+    /// the IMEx where it went wrong is printed with carets under the span, followed by the error.
+    pub fn render(&self, imex: &str) -> String {
+        let start = self.span.start.min(imex.len());
+        let line_start = imex[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = imex[start..]
+            .find('\n')
+            .map_or(imex.len(), |newline| start + newline);
+        let line = &imex[line_start..line_end];
+        let column = imex[line_start..start].chars().count();
+        let width = imex
+            .get(start..self.span.end.min(line_end))
+            .map_or(0, |span| span.chars().count())
+            .max(1);
+        format!(
+            "{}\n{}{}\n{}",
+            line,
+            " ".repeat(column),
+            "^".repeat(width),
+            self
+        )
+    }
+}
+
+/// Decides what went wrong at `position`, from the text before it and what was expected there.
+fn classify(
+    imex_str: &str,
+    position: usize,
+    expected: &[Expected],
+) -> (ParseErrorKind, Range<usize>) {
+    let mut open = vec![];
+    for (index, c) in imex_str[..position].char_indices() {
+        match c {
+            '(' | '[' | '<' | '{' => open.push((index, c)),
+            ')' | ']' | '>' | '}' => {
+                open.pop();
+            }
+            _ => (),
+        }
+    }
+    let found = imex_str[position..].chars().next();
+    let here = position..position + found.map_or(0, char::len_utf8);
+
+    if let Some(&(brace, '{')) = open.last() {
+        let end = imex_str[brace..]
+            .find('}')
+            .map_or(imex_str.len(), |close| brace + close + 1);
+        return (ParseErrorKind::BadQuantifier, brace..end);
+    }
+    match found {
+        Some('*') | Some('+') | Some('?') | Some('{') => (ParseErrorKind::BadQuantifier, here),
+        Some(close @ ')') | Some(close @ ']') | Some(close @ '>') | Some(close @ '}')
+            if open.last().map(|&(_, c)| closer(c)) != Some(close) =>
+        {
+            (ParseErrorKind::UnbalancedParen, here)
+        }
+        Some(c) => (ParseErrorKind::UnexpectedChar(c), here),
+        None => match open.last() {
+            Some(&(index, c)) if expected.contains(&Expected::Char(closer(c))) => {
+                (ParseErrorKind::UnbalancedParen, index..index + 1)
+            }
+            _ => (ParseErrorKind::UnexpectedEnd, here),
+        },
+    }
+}
+
+fn closer(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        '<' => '>',
+        _ => '}',
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnbalancedParen => write!(f, "unbalanced parenthesis"),
+            ParseErrorKind::BadQuantifier => write!(f, "bad quantifier"),
+            ParseErrorKind::Overflow => write!(f, "number is too large"),
+            ParseErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of IMEx"),
+            ParseErrorKind::DuplicateGroup(name) => {
+                write!(f, "group \"{}\" is defined more than once", name)
+            }
+            ParseErrorKind::UndefinedGroup(name) => {
+                write!(f, "call to undefined group \"{}\"", name)
+            }
+            ParseErrorKind::RecursiveGroup(name) => write!(f, "group \"{}\" calls itself", name),
+        }
+    }
+}
+
+/// Prints the kind of the error and its span, then the expected tokens, if there are any.
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.kind, self.span.start, self.span.end)?;
+        if let Some((last, rest)) = self.expected.split_last() {
+            write!(f, ", expected ")?;
+            if !rest.is_empty() {
+                write!(f, "{} or ", rest.join(", "))?;
+            }
+            write!(f, "{}", last)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl From<ParseError> for io::Error {
+    fn from(error: ParseError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_lists_expected_tokens() {
+        let expected = vec!["')'".to_string(), "'|'".to_string(), "a digit".to_string()];
+        let error = ParseError::new(ParseErrorKind::UnexpectedEnd, 3..3, expected);
+        assert_eq!(
+            error.to_string(),
+            "unexpected end of IMEx at 3..3, expected ')', '|' or a digit"
+        );
+    }
+
+    #[test]
+    fn render_points_at_span_in_its_line() {
+        let imex = "(0\n  1{3,1}) # comment";
+        let error = ParseError::new(ParseErrorKind::BadQuantifier, 6..11, vec![]);
+        assert_eq!(
+            error.render(imex),
+            "  1{3,1}) # comment\n   ^^^^^\nbad quantifier at 6..11"
+        );
+    }
+
+    #[test]
+    fn render_points_past_end() {
+        let error = ParseError::new(ParseErrorKind::UnexpectedEnd, 2..2, vec![]);
+        assert_eq!(
+            error.render("~("),
+            "~(\n  ^\nunexpected end of IMEx at 2..2"
+        );
+    }
+
+    #[test]
+    fn parse_error_converts_to_io_error() {
+        let error = ParseError::new(ParseErrorKind::Overflow, 1..4, vec![]);
+        let io_error = io::Error::from(error);
+        assert_eq!(io_error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(io_error.to_string(), "number is too large at 1..4");
+    }
+}
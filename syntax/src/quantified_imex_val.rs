@@ -0,0 +1,97 @@
+use crate::{Failure, IMEx, IMExVal, Marked, ParserCombinator, Quantifier, Resolved};
+use nom::IResult;
+use std::{collections::HashMap, fmt, io::Result};
+
+/// An [`IMExVal`](./enum.IMExVal.html) that has been quantified, for use in a parsed
+/// [`IMEx`](./struct.IMEx.html).
+#[derive(PartialEq, Debug, Clone)]
+pub struct QuantifiedIMExVal {
+    val: IMExVal,
+    quantifier: Quantifier,
+}
+
+impl QuantifiedIMExVal {
+    pub fn new(val: IMExVal, quantifier: Quantifier) -> QuantifiedIMExVal {
+        QuantifiedIMExVal { val, quantifier }
+    }
+
+    /// Returns an immutable reference to self.val
+    pub fn get_val(&self) -> &IMExVal {
+        &self.val
+    }
+
+    /// Returns an immutable reference to self.quantifier
+    pub fn get_quantifier(&self) -> &Quantifier {
+        &self.quantifier
+    }
+
+    /// Resolves the names in self.val into indices of `names`.
+    pub(crate) fn resolve_names<S: AsRef<str>>(self, names: &[S]) -> Result<QuantifiedIMExVal> {
+        Ok(QuantifiedIMExVal::new(
+            self.val.resolve_names(names)?,
+            self.quantifier,
+        ))
+    }
+
+    /// Binds the parameters in self.quantifier and self.val to their counts in `params`.
+    pub(crate) fn bind_params(self, params: &HashMap<String, usize>) -> Result<QuantifiedIMExVal> {
+        Ok(QuantifiedIMExVal::new(
+            self.val.bind_params(params)?,
+            self.quantifier.bind_params(params)?,
+        ))
+    }
+
+    /// Resolves the calls to named groups in self.val.
+    pub(crate) fn resolve_calls(
+        self,
+        definitions: &HashMap<String, IMEx>,
+        callers: &mut Vec<String>,
+    ) -> Resolved<QuantifiedIMExVal> {
+        Ok(QuantifiedIMExVal::new(
+            self.val.resolve_calls(definitions, callers)?,
+            self.quantifier,
+        ))
+    }
+}
+
+/// Prints the value followed by its quantifier. Zero-width values can't be quantified, so their
+/// quantifier isn't printed.
+impl fmt::Display for QuantifiedIMExVal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.val.is_zero_width() {
+            write!(f, "{}", self.val)
+        } else {
+            write!(f, "{}{}", self.val, self.quantifier)
+        }
+    }
+}
+
+impl ParserCombinator for QuantifiedIMExVal {
+    fn parse_marked(input: &str) -> IResult<&str, (QuantifiedIMExVal, Vec<Marked>), Failure> {
+        let (input, (val, marks)) = IMExVal::parse_marked(input)?;
+        // Zero-width values can't be repeated, so they don't take a quantifier.
+        if val.is_zero_width() {
+            let val = QuantifiedIMExVal::new(val, Quantifier::Finite(1));
+            return Ok((input, (val, marks)));
+        }
+        let (input, quantifier) = Quantifier::parse(input)?;
+        Ok((input, (QuantifiedIMExVal::new(val, quantifier), marks)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qimex_val_prints_val_and_quantifier() {
+        let repeated = QuantifiedIMExVal::new(IMExVal::Single(3), Quantifier::Range(1, Some(2)));
+        let anchor = QuantifiedIMExVal::new(
+            IMExVal::Anchor(Box::new(IMExVal::Single(0))),
+            Quantifier::Finite(1),
+        );
+
+        assert_eq!(repeated.to_string(), "3{1,2}");
+        assert_eq!(anchor.to_string(), "$0");
+    }
+}
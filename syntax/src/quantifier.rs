@@ -0,0 +1,302 @@
+use crate::{parse_identifier, parse_number, Failure, Marked, ParserCombinator};
+use nom::{
+    branch::alt,
+    character::complete::char,
+    combinator::{cut, opt, verify},
+    sequence::{delimited, preceded, separated_pair, terminated},
+    IResult,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{Error, ErrorKind::InvalidInput, Result},
+};
+
+/// Represents a quantifier in a parsed [`IMEx`](./struct.IMEx.html). Either Finite (`{x}`), in
+/// which case a range is contained, Infinite (`*`), AtLeastOnce (`+`), Optional (`?`), or Range
+/// (`{m,n}`, `{m,}` or `{,n}`), in which case a minimum and an optional maximum are contained.
+///
+/// A Param (`{$name}`) is a Finite quantifier whose count is a named parameter, which has to be
+/// bound with [`IMEx::bind_params`](./struct.IMEx.html#method.bind_params) before merging.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Quantifier {
+    Infinite,
+    AtLeastOnce,
+    Optional,
+    Finite(usize),
+    Range(usize, Option<usize>),
+    Param(String),
+}
+
+impl Quantifier {
+    /// The number of repetitions this quantifier requires when merging strictly. This is `x` for
+    /// Finite (`{x}`), `m` for Range (`{m,n}`), 1 for AtLeastOnce (`+`), and 0 for Infinite (`*`)
+    /// and Optional (`?`).
+    pub fn minimum(&self) -> usize {
+        match *self {
+            Quantifier::Infinite | Quantifier::Optional => 0,
+            Quantifier::AtLeastOnce => 1,
+            Quantifier::Finite(n) => n,
+            Quantifier::Range(min, _) => min,
+            Quantifier::Param(_) => 0,
+        }
+    }
+
+    /// The number of repetitions this quantifier allows, or None if it allows any number. This is
+    /// `x` for Finite (`{x}`), `n` for Range (`{m,n}`), 1 for Optional (`?`), and None for
+    /// Infinite (`*`) and AtLeastOnce (`+`). An unbound Param allows no repetitions.
+    pub fn maximum(&self) -> Option<usize> {
+        match *self {
+            Quantifier::Infinite | Quantifier::AtLeastOnce => None,
+            Quantifier::Optional => Some(1),
+            Quantifier::Finite(n) => Some(n),
+            Quantifier::Range(_, max) => max,
+            Quantifier::Param(_) => Some(0),
+        }
+    }
+
+    /// Replaces a Param with a Finite quantifier, using the count bound to its name in `params`.
+    ///
+    /// # Error
+    /// Results in an error if the Param's name isn't in `params`.
+    pub(crate) fn bind_params(self, params: &HashMap<String, usize>) -> Result<Quantifier> {
+        match self {
+            Quantifier::Param(name) => match params.get(&name) {
+                Some(count) => Ok(Quantifier::Finite(*count)),
+                None => Err(Error::new(
+                    InvalidInput,
+                    format!("IMEx parameter \"{}\" isn't bound", name),
+                )),
+            },
+            quantifier => Ok(quantifier),
+        }
+    }
+}
+
+impl Iterator for Quantifier {
+    type Item = ();
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match *self {
+            Quantifier::Finite(0) | Quantifier::Range(_, Some(0)) | Quantifier::Param(_) => None,
+            Quantifier::Finite(n) => {
+                *self = Quantifier::Finite(n - 1);
+                Some(())
+            }
+            Quantifier::Range(min, max) => {
+                *self = Quantifier::Range(min.saturating_sub(1), max.map(|max| max - 1));
+                Some(())
+            }
+            Quantifier::Optional => {
+                *self = Quantifier::Finite(0);
+                Some(())
+            }
+            _ => Some(()),
+        }
+    }
+}
+
+/// Prints the quantifier as it is written in an IMEx. Finite(1) is printed as nothing, since it is
+/// the quantifier of a value without one.
+impl fmt::Display for Quantifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Quantifier::Infinite => write!(f, "*"),
+            Quantifier::AtLeastOnce => write!(f, "+"),
+            Quantifier::Optional => write!(f, "?"),
+            Quantifier::Finite(1) => Ok(()),
+            Quantifier::Finite(n) => write!(f, "{{{}}}", n),
+            Quantifier::Range(min, Some(max)) => write!(f, "{{{},{}}}", min, max),
+            Quantifier::Range(min, None) => write!(f, "{{{},}}", min),
+            Quantifier::Param(name) => write!(f, "{{${}}}", name),
+        }
+    }
+}
+
+fn parse_count(input: &str) -> IResult<&str, usize, Failure> {
+    parse_number(input)
+}
+
+fn parse_finite_quantifier(input: &str) -> IResult<&str, Quantifier, Failure> {
+    let (input, count) = terminated(parse_number, char('}'))(input)?;
+    Ok((input, Quantifier::Finite(count)))
+}
+
+fn parse_param_quantifier(input: &str) -> IResult<&str, Quantifier, Failure> {
+    let (input, name) = delimited(char('$'), parse_identifier, char('}'))(input)?;
+    Ok((input, Quantifier::Param(name.to_string())))
+}
+
+fn parse_range_quantifier(input: &str) -> IResult<&str, Quantifier, Failure> {
+    let (input, (min, max)) = verify(
+        terminated(
+            separated_pair(opt(parse_count), char(','), opt(parse_count)),
+            char('}'),
+        ),
+        |bounds: &(Option<usize>, Option<usize>)| match bounds {
+            (None, None) => false,
+            (Some(min), Some(max)) => min <= max,
+            _ => true,
+        },
+    )(input)?;
+    Ok((input, Quantifier::Range(min.unwrap_or(0), max)))
+}
+
+/// Parses a quantifier in braces. Once the brace is opened, the quantifier has to be finished,
+/// without backtracking, since nothing else starts with a brace.
+fn parse_braced_quantifier(input: &str) -> IResult<&str, Quantifier, Failure> {
+    preceded(
+        char('{'),
+        cut(alt((
+            parse_param_quantifier,
+            parse_range_quantifier,
+            parse_finite_quantifier,
+        ))),
+    )(input)
+}
+
+fn parse_infinite_quantifier(input: &str) -> IResult<&str, Quantifier, Failure> {
+    let (input, _) = char('*')(input)?;
+    Ok((input, Quantifier::Infinite))
+}
+
+fn parse_at_least_once_quantifier(input: &str) -> IResult<&str, Quantifier, Failure> {
+    let (input, _) = char('+')(input)?;
+    Ok((input, Quantifier::AtLeastOnce))
+}
+
+fn parse_optional_quantifier(input: &str) -> IResult<&str, Quantifier, Failure> {
+    let (input, _) = char('?')(input)?;
+    Ok((input, Quantifier::Optional))
+}
+
+impl ParserCombinator for Quantifier {
+    fn parse_marked(input: &str) -> IResult<&str, (Quantifier, Vec<Marked>), Failure> {
+        let (input, quantifier) = opt(alt((
+            parse_infinite_quantifier,
+            parse_at_least_once_quantifier,
+            parse_optional_quantifier,
+            parse_braced_quantifier,
+        )))(input)?;
+        Ok((input, (quantifier.unwrap_or(Quantifier::Finite(1)), vec![])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn three_quantifier_iterates_thrice() {
+        let mut r = Quantifier::Finite(3);
+        assert_eq!(r.next(), Some(()));
+        assert_eq!(r, Quantifier::Finite(2));
+        assert_eq!(r.next(), Some(()));
+        assert_eq!(r, Quantifier::Finite(1));
+        assert_eq!(r.next(), Some(()));
+        assert_eq!(r, Quantifier::Finite(0));
+        assert_eq!(r.next(), None);
+    }
+
+    #[test]
+    fn zero_quantifier_gives_immediate_none() {
+        let mut r = Quantifier::Finite(0);
+        assert_eq!(r.next(), None);
+    }
+
+    #[test]
+    fn bounded_range_quantifier_iterates_up_to_max() {
+        let mut r = Quantifier::Range(1, Some(3));
+        assert_eq!(r.next(), Some(()));
+        assert_eq!(r, Quantifier::Range(0, Some(2)));
+        assert_eq!(r.next(), Some(()));
+        assert_eq!(r, Quantifier::Range(0, Some(1)));
+        assert_eq!(r.next(), Some(()));
+        assert_eq!(r, Quantifier::Range(0, Some(0)));
+        assert_eq!(r.next(), None);
+    }
+
+    #[test]
+    fn unbounded_range_quantifier_iterates_a_lot() {
+        let mut r = Quantifier::Range(3, None);
+        for _ in 0..100 {
+            assert_eq!(r.next(), Some(()));
+        }
+        assert_eq!(r, Quantifier::Range(0, None));
+    }
+
+    #[test]
+    fn quantifier_min_is_required_repetitions() {
+        assert_eq!(Quantifier::Infinite.minimum(), 0);
+        assert_eq!(Quantifier::AtLeastOnce.minimum(), 1);
+        assert_eq!(Quantifier::Optional.minimum(), 0);
+        assert_eq!(Quantifier::Finite(4).minimum(), 4);
+        assert_eq!(Quantifier::Range(2, Some(5)).minimum(), 2);
+        assert_eq!(Quantifier::Range(0, Some(5)).minimum(), 0);
+    }
+
+    #[test]
+    fn quantifier_max_is_allowed_repetitions() {
+        assert_eq!(Quantifier::Infinite.maximum(), None);
+        assert_eq!(Quantifier::AtLeastOnce.maximum(), None);
+        assert_eq!(Quantifier::Optional.maximum(), Some(1));
+        assert_eq!(Quantifier::Finite(4).maximum(), Some(4));
+        assert_eq!(Quantifier::Range(2, Some(5)).maximum(), Some(5));
+        assert_eq!(Quantifier::Range(2, None).maximum(), None);
+        assert_eq!(Quantifier::Param("n".to_string()).maximum(), Some(0));
+    }
+
+    #[test]
+    fn optional_quantifier_iterates_once() {
+        let mut r = Quantifier::Optional;
+        assert_eq!(r.next(), Some(()));
+        assert_eq!(r.next(), None);
+    }
+
+    #[test]
+    fn at_least_once_quantifier_iterates_a_lot() {
+        let mut r = Quantifier::AtLeastOnce;
+        for _ in 0..100 {
+            assert_eq!(r.next(), Some(()));
+        }
+    }
+
+    #[test]
+    fn infinite_quantifier_iterates_a_lot() {
+        let mut r = Quantifier::Infinite;
+        for _ in 0..100 {
+            assert_eq!(r.next(), Some(()));
+        }
+    }
+
+    #[test]
+    fn bound_param_quantifier_becomes_finite() -> Result<()> {
+        let mut params = HashMap::new();
+        params.insert("n".to_string(), 4);
+
+        assert_eq!(
+            Quantifier::Param("n".to_string()).bind_params(&params)?,
+            Quantifier::Finite(4)
+        );
+        assert_eq!(
+            Quantifier::Infinite.bind_params(&params)?,
+            Quantifier::Infinite
+        );
+        Quantifier::Param("m".to_string())
+            .bind_params(&params)
+            .unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn quantifier_prints_as_written() {
+        assert_eq!(Quantifier::Infinite.to_string(), "*");
+        assert_eq!(Quantifier::AtLeastOnce.to_string(), "+");
+        assert_eq!(Quantifier::Optional.to_string(), "?");
+        assert_eq!(Quantifier::Finite(1).to_string(), "");
+        assert_eq!(Quantifier::Finite(0).to_string(), "{0}");
+        assert_eq!(Quantifier::Range(2, Some(5)).to_string(), "{2,5}");
+        assert_eq!(Quantifier::Range(0, None).to_string(), "{0,}");
+        assert_eq!(Quantifier::Param("n".to_string()).to_string(), "{$n}");
+    }
+}
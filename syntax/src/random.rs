@@ -0,0 +1,138 @@
+//! Generates random IMExes, for the property tests of printing, parsing and analysing them, and
+//! of merging with them in imex. It's only built for tests, or with the `random` feature. Calls
+//! aren't generated, since they need a matching named group to parse.
+use crate::{
+    Conditional, IMEx, IMExVal, QuantifiedIMExVal, Quantifier, SortedMerge, SourceClass,
+    WeightedChoice,
+};
+
+/// A small pseudo-random number generator, so the same seed always generates the same IMExes.
+///
+/// This is an implementation of xorshift64*.
+#[derive(Debug, Clone)]
+pub struct Numbers {
+    state: u64,
+}
+
+impl Numbers {
+    /// Constructs a Numbers that gives the same numbers each time it is constructed with `seed`.
+    pub fn new(seed: u64) -> Numbers {
+        // The state of a xorshift generator can't be 0, or it would stay 0.
+        Numbers {
+            state: seed.wrapping_add(0x9e37_79b9_7f4a_7c15).max(1),
+        }
+    }
+
+    /// Returns the next pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns a pseudo-random number that is less than `bound`, which can't be 0.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+fn name(numbers: &mut Numbers) -> String {
+    let len = 1 + numbers.below(4);
+    (0..len)
+        .map(
+            |position| match numbers.below(if position == 0 { 27 } else { 37 }) {
+                26 => '_',
+                c @ 27..=36 => (b'0' + (c - 27) as u8) as char,
+                c => (b'a' + c as u8) as char,
+            },
+        )
+        .collect()
+}
+
+/// Generates a random Quantifier.
+pub fn quantifier(numbers: &mut Numbers) -> Quantifier {
+    let min = numbers.below(12);
+    match numbers.below(7) {
+        0 => Quantifier::Infinite,
+        1 => Quantifier::AtLeastOnce,
+        2 => Quantifier::Optional,
+        3 => Quantifier::Finite(1),
+        4 => Quantifier::Finite(min),
+        5 => Quantifier::Range(min, [None, Some(min + numbers.below(5))][numbers.below(2)]),
+        _ => Quantifier::Param(name(numbers)),
+    }
+}
+
+fn source(numbers: &mut Numbers) -> IMExVal {
+    match numbers.below(3) {
+        0 => IMExVal::Single(numbers.below(10) as isize),
+        1 => IMExVal::Single(numbers.below(40) as isize - 20),
+        _ => IMExVal::Named(name(numbers)),
+    }
+}
+
+fn class(numbers: &mut Numbers) -> SourceClass {
+    match numbers.below(4) {
+        0 => SourceClass::any(),
+        // A class of one negative index can't be written, since it would be a Single.
+        1 => SourceClass::new(vec![numbers.below(20) as isize]),
+        _ => SourceClass::new(
+            (0..2 + numbers.below(3))
+                .map(|_| numbers.below(30) as isize - 10)
+                .collect(),
+        ),
+    }
+}
+
+/// Generates a random value, with groups nested at most `depth` deep.
+pub fn val(numbers: &mut Numbers, depth: usize) -> IMExVal {
+    match numbers.below(if depth == 0 { 4 } else { 11 }) {
+        0 => source(numbers),
+        1 => IMExVal::Class(class(numbers)),
+        2 => IMExVal::Sorted(SortedMerge::new(
+            (0..1 + numbers.below(3)).map(|_| source(numbers)).collect(),
+        )),
+        3 => IMExVal::Anchor(Box::new(source(numbers))),
+        4 => IMExVal::Group(imex(numbers, depth - 1)),
+        // Groups can't share a name, so they're named by a random number.
+        5 => IMExVal::NamedGroup(format!("g{}", numbers.next_u64()), imex(numbers, depth - 1)),
+        6 => IMExVal::Alternation(
+            (0..2 + numbers.below(2))
+                .map(|_| imex(numbers, depth - 1))
+                .collect(),
+        ),
+        7 => IMExVal::Weighted(WeightedChoice::new(
+            (0..1 + numbers.below(3))
+                .map(|_| (imex(numbers, depth - 1), 1 + numbers.below(100)))
+                .collect(),
+        )),
+        8 => IMExVal::Discard(Box::new(val(numbers, depth - 1))),
+        9 => IMExVal::Conditional(Conditional::new(
+            source(numbers),
+            numbers.below(2) == 0,
+            imex(numbers, depth - 1),
+        )),
+        _ => source(numbers),
+    }
+}
+
+/// Generates a random quantified value, with groups nested at most `depth` deep.
+pub fn qimex_val(numbers: &mut Numbers, depth: usize) -> QuantifiedIMExVal {
+    let val = val(numbers, depth);
+    let quantifier = if val.is_zero_width() {
+        Quantifier::Finite(1)
+    } else {
+        quantifier(numbers)
+    };
+    QuantifiedIMExVal::new(val, quantifier)
+}
+
+/// Generates a random IMEx, with groups nested at most `depth` deep.
+pub fn imex(numbers: &mut Numbers, depth: usize) -> IMEx {
+    IMEx::new(
+        (0..numbers.below(4))
+            .map(|_| qimex_val(numbers, depth))
+            .collect(),
+    )
+}
@@ -0,0 +1,238 @@
+use crate::{Conditional, IMEx, IMExVal, QuantifiedIMExVal, Quantifier, WeightedChoice};
+
+impl IMEx {
+    /// Returns an equivalent [`IMEx`](./struct.IMEx.html) with fewer values, which gives the same
+    /// items when merging, and the same errors when merging strictly. Groups of one value are
+    /// flattened, so `(0)` becomes `0` and `((01))*` becomes `(01)*`, and groups that are used
+    /// once are flattened into the IMEx around them. Repetitions of the same value are merged,
+    /// so `000` becomes `0{3}`, and values repeated `{0}` times are dropped, as are empty groups
+    /// that can be repeated no times.
+    ///
+    /// A group isn't flattened if it has an anchor, since an anchor that fails ends the group
+    /// rather than the IMEx around it. Merging strictly, a group that's used once requires an
+    /// item, so a group isn't flattened if none of its values requires one. For the same
+    /// reason, `()` isn't dropped.
+    ///
+    /// Values with classes or weighted choices aren't merged, since their repetitions don't
+    /// share a rotation or random choices with each other.
+    ///
+    /// # Example
+    /// ```
+    /// use imex_syntax::IMEx;
+    /// use std::convert::TryFrom;
+    ///
+    /// let imex = IMEx::try_from("((01))*0{0}(0)0{2}(1()*2{3})").expect("Invalid IMEx");
+    ///
+    /// assert_eq!(imex.simplify().to_string(), "(01)*0{3}12{3}");
+    /// ```
+    pub fn simplify(&self) -> IMEx {
+        let mut vals: Vec<QuantifiedIMExVal> = vec![];
+        for val in self.vals.iter().map(QuantifiedIMExVal::simplify) {
+            if val.is_dropped() || vals.last_mut().map_or(false, |last| last.merge(&val)) {
+                continue;
+            }
+            match val.get_val() {
+                IMExVal::Group(imex)
+                    if *val.get_quantifier() == Quantifier::Finite(1) && imex.is_spliceable() =>
+                {
+                    for inner in &imex.vals {
+                        if !vals.last_mut().map_or(false, |last| last.merge(inner)) {
+                            vals.push(inner.clone());
+                        }
+                    }
+                }
+                _ => vals.push(val),
+            }
+        }
+        IMEx::new(vals)
+    }
+
+    /// Whether the values of this IMEx can replace a group of it that's used once. None of them
+    /// can fail, and one of them has to require an item, as the group would when merging
+    /// strictly.
+    fn is_spliceable(&self) -> bool {
+        self.vals.iter().all(|val| !val.get_val().can_fail())
+            && self
+                .vals
+                .iter()
+                .any(|val| val.get_quantifier().minimum() > 0)
+    }
+}
+
+impl QuantifiedIMExVal {
+    fn simplify(&self) -> QuantifiedIMExVal {
+        QuantifiedIMExVal::new(self.get_val().simplify(), self.get_quantifier().clone())
+    }
+
+    /// Merges `next` into this value if they're repetitions of the same value, returning
+    /// whether they were merged.
+    fn merge(&mut self, next: &QuantifiedIMExVal) -> bool {
+        let count = match (self.get_quantifier(), next.get_quantifier()) {
+            (Quantifier::Finite(count), Quantifier::Finite(more)) => count.checked_add(*more),
+            _ => None,
+        };
+        match count {
+            Some(count) if self.get_val() == next.get_val() && next.get_val().is_mergeable() => {
+                *self = QuantifiedIMExVal::new(next.get_val().clone(), Quantifier::Finite(count));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this value never does anything, so it can be dropped. An unbound Param allows no
+    /// repetitions, but it's kept so it can be bound.
+    fn is_dropped(&self) -> bool {
+        let quantifier = self.get_quantifier();
+        match self.get_val() {
+            _ if matches!(quantifier, Quantifier::Param(_)) => false,
+            _ if quantifier.maximum() == Some(0) => true,
+            IMExVal::Group(imex) => imex.vals.is_empty() && quantifier.minimum() == 0,
+            _ => false,
+        }
+    }
+}
+
+impl IMExVal {
+    fn simplify(&self) -> IMExVal {
+        match self {
+            IMExVal::Group(imex) => {
+                let imex = imex.simplify();
+                match imex.vals.as_slice() {
+                    [only]
+                        if *only.get_quantifier() == Quantifier::Finite(1)
+                            && !only.get_val().can_fail() =>
+                    {
+                        only.get_val().clone()
+                    }
+                    _ => IMExVal::Group(imex),
+                }
+            }
+            IMExVal::NamedGroup(name, imex) => IMExVal::NamedGroup(name.clone(), imex.simplify()),
+            IMExVal::Call(name, imex) => IMExVal::Call(name.clone(), imex.simplify()),
+            IMExVal::Alternation(imexes) => {
+                IMExVal::Alternation(imexes.iter().map(IMEx::simplify).collect())
+            }
+            IMExVal::Weighted(choice) => IMExVal::Weighted(WeightedChoice::new(
+                choice
+                    .get_alternatives()
+                    .iter()
+                    .map(|(imex, weight)| (imex.simplify(), *weight))
+                    .collect(),
+            )),
+            IMExVal::Discard(val) => IMExVal::Discard(Box::new(val.simplify())),
+            IMExVal::Conditional(conditional) => IMExVal::Conditional(Conditional::new(
+                conditional.get_source().clone(),
+                conditional.is_negated(),
+                conditional.get_imex().simplify(),
+            )),
+            val => val.clone(),
+        }
+    }
+
+    /// Whether stepping this value can fail, which ends the IMEx it's in.
+    fn can_fail(&self) -> bool {
+        match self {
+            IMExVal::Anchor(_) => true,
+            IMExVal::Discard(val) => val.can_fail(),
+            _ => false,
+        }
+    }
+
+    /// Whether repetitions of this value can be merged into one quantified value. Each class and
+    /// weighted choice keeps its own rotation or random choices, and named groups can't be
+    /// defined twice.
+    fn is_mergeable(&self) -> bool {
+        match self {
+            IMExVal::Class(_) | IMExVal::Weighted(_) | IMExVal::NamedGroup(_, _) => false,
+            IMExVal::Discard(val) => val.is_mergeable(),
+            val => {
+                !val.can_fail()
+                    && val
+                        .inner_imexes()
+                        .into_iter()
+                        .all(|imex| imex.vals.iter().all(|inner| inner.get_val().is_mergeable()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{self, Numbers};
+    use std::{convert::TryFrom, io::Result};
+
+    fn simplified(imex_str: &str) -> Result<String> {
+        Ok(IMEx::try_from(imex_str)?.simplify().to_string())
+    }
+
+    #[test]
+    fn single_value_groups_are_flattened() -> Result<()> {
+        assert_eq!(simplified("(0)*")?, "0*");
+        assert_eq!(simplified("((01))*")?, "(01)*");
+        assert_eq!(simplified("~(1)(?0:(2))")?, "~1(?0:2)");
+        assert_eq!(simplified("(((0)))+")?, "0+");
+        Ok(())
+    }
+
+    #[test]
+    fn groups_used_once_are_spliced() -> Result<()> {
+        assert_eq!(simplified("0(12)3")?, "0123");
+        assert_eq!(simplified("(0(1(2)))")?, "012");
+        // Merging strictly, these groups require an item that their values don't.
+        assert_eq!(simplified("(0?)1")?, "(0?)1");
+        assert_eq!(simplified("(0*1?)")?, "(0*1?)");
+        Ok(())
+    }
+
+    #[test]
+    fn groups_with_anchors_are_kept() -> Result<()> {
+        assert_eq!(simplified("($0)1")?, "($0)1");
+        assert_eq!(simplified("(1$0)*")?, "(1$0)*");
+        assert_eq!(simplified("(~$0)")?, "(~$0)");
+        Ok(())
+    }
+
+    #[test]
+    fn repetitions_are_merged() -> Result<()> {
+        assert_eq!(simplified("000")?, "0{3}");
+        assert_eq!(simplified("0{2}0(0)")?, "0{4}");
+        assert_eq!(simplified("(01){3}(01)")?, "(01){4}");
+        assert_eq!(simplified("(01)(01){3}")?, "01(01){3}");
+        assert_eq!(simplified("0*00")?, "0*0{2}");
+        assert_eq!(simplified("aa[bb]")?, "a{2}[bb]");
+        Ok(())
+    }
+
+    #[test]
+    fn stateful_repetitions_are_kept() -> Result<()> {
+        assert_eq!(simplified("..")?, "..");
+        assert_eq!(simplified("(0%1|1%1)(0%1|1%1)")?, "(0%1|1%1)(0%1|1%1)");
+        assert_eq!(simplified("(0.)(0.)")?, "0.0.");
+        Ok(())
+    }
+
+    #[test]
+    fn empty_values_are_dropped() -> Result<()> {
+        assert_eq!(simplified("0{0}1(2){0}3{,0}")?, "1");
+        assert_eq!(simplified("0()*()?(0{0})*")?, "0");
+        assert_eq!(simplified("0{$n}")?, "0{$n}");
+        assert_eq!(simplified("0()")?, "0()");
+        Ok(())
+    }
+
+    #[test]
+    fn simplified_random_imexes_reparse_and_stay_simplified() -> Result<()> {
+        let mut numbers = Numbers::new(23);
+        for _ in 0..2000 {
+            let imex = random::imex(&mut numbers, 3);
+            let simplified = imex.simplify();
+            let reparsed = IMEx::try_from(simplified.to_string().as_str())?;
+
+            assert_eq!(reparsed, simplified, "{}", imex);
+            assert_eq!(simplified.simplify(), simplified, "{}", imex);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    parse_source_imex_val, repeated_then, split_marks, Failure, IMExVal, Marked, ParserCombinator,
+};
+use nom::{
+    character::complete::char,
+    sequence::{pair, preceded},
+    IResult,
+};
+use std::{fmt, io::Result};
+
+/// Represents a sorted merge in a parsed [`IMEx`](./struct.IMEx.html), written like `<012>`.
+/// Iterating a SortedMerge gives the smallest next item of the iterators it lists, so `<012>*`
+/// merges three sorted iterators into one sorted iterator.
+///
+/// Items are compared with the comparator given to
+/// [`IMExIter::sorted_by`](https://docs.rs/imex/*/imex/struct.IMExIter.html#method.sorted_by),
+/// and ties go to the iterator listed first. The other constructors of IMExIter reject IMExes
+/// with sorted merges, since they take no comparator.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SortedMerge {
+    sources: Vec<IMExVal>,
+}
+
+impl SortedMerge {
+    /// Construct a new SortedMerge of the iterators referred to by `sources`, which are Single
+    /// or Named [`IMExVal`](./enum.IMExVal.html)s.
+    pub fn new(sources: Vec<IMExVal>) -> SortedMerge {
+        SortedMerge { sources }
+    }
+
+    /// Returns an immutable reference to the values referring to the iterators being merged.
+    pub fn get_sources(&self) -> &[IMExVal] {
+        &self.sources
+    }
+
+    /// Resolves the names in self.sources into indices of `names`.
+    pub(crate) fn resolve_names<S: AsRef<str>>(self, names: &[S]) -> Result<SortedMerge> {
+        Ok(SortedMerge::new(
+            self.sources
+                .into_iter()
+                .map(|source| source.resolve_names(names))
+                .collect::<Result<Vec<IMExVal>>>()?,
+        ))
+    }
+}
+
+impl fmt::Display for SortedMerge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<")?;
+        self.sources
+            .iter()
+            .try_for_each(|source| write!(f, "{}", source))?;
+        write!(f, ">")
+    }
+}
+
+impl ParserCombinator for SortedMerge {
+    fn parse_marked(input: &str) -> IResult<&str, (SortedMerge, Vec<Marked>), Failure> {
+        let (input, (first, mut sources)) = preceded(
+            char('<'),
+            pair(
+                parse_source_imex_val,
+                repeated_then(parse_source_imex_val, char('>')),
+            ),
+        )(input)?;
+        sources.insert(0, first);
+        let (sources, marks) = split_marks(sources);
+        Ok((input, (SortedMerge::new(sources), marks)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn singles(indices: &[isize]) -> Vec<IMExVal> {
+        indices
+            .iter()
+            .map(|index| IMExVal::Single(*index))
+            .collect()
+    }
+
+    #[test]
+    fn sorted_merge_parses() {
+        assert_eq!(
+            SortedMerge::parse("<01[12]>*"),
+            Ok(("*", SortedMerge::new(singles(&[0, 1, 12]))))
+        );
+        assert_eq!(
+            SortedMerge::parse("<a[bee]>"),
+            Ok((
+                "",
+                SortedMerge::new(vec![
+                    IMExVal::Named("a".to_string()),
+                    IMExVal::Named("bee".to_string())
+                ])
+            ))
+        );
+    }
+
+    #[test]
+    fn bad_sorted_merge_fails() {
+        SortedMerge::parse("<>").unwrap_err();
+
+        SortedMerge::parse("<0,1>").unwrap_err();
+
+        SortedMerge::parse("<0(1)>").unwrap_err();
+
+        SortedMerge::parse("<01").unwrap_err();
+    }
+
+    #[test]
+    fn sorted_merge_prints_as_written() {
+        let sorted = SortedMerge::new(vec![
+            IMExVal::Single(0),
+            IMExVal::Single(12),
+            IMExVal::Named("bee".to_string()),
+        ]);
+
+        assert_eq!(sorted.to_string(), "<0[12][bee]>");
+    }
+}
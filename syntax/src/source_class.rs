@@ -0,0 +1,190 @@
+use crate::{
+    parse_number, parse_signed_number, repeated_then, unmarked, Failure, Mark, Marked,
+    ParserCombinator,
+};
+use nom::{
+    branch::alt,
+    character::complete::char,
+    combinator::{map, verify},
+    sequence::{pair, preceded, separated_pair},
+    IResult,
+};
+use std::{fmt, ops::RangeInclusive};
+
+/// Represents a class of iterators in a parsed [`IMEx`](./struct.IMEx.html), written like
+/// `[0,2,4]` or `[0-5]`, or `.` for a class of every iterator being merged. Iterating a
+/// SourceClass gives one item from the first iterator in the class that isn't exhausted.
+///
+/// The starting point rotates through the class each time an item is given, so repeating a
+/// SourceClass takes items from its iterators fairly. This rotation is kept with the iterators
+/// being merged, so it carries over between the repetitions of a quantifier.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SourceClass {
+    indices: Option<Vec<isize>>,
+}
+
+impl SourceClass {
+    /// Construct a new SourceClass from the indices of the iterators in the class, in the order
+    /// they should be rotated through.
+    pub fn new(indices: Vec<isize>) -> SourceClass {
+        SourceClass {
+            indices: Some(indices),
+        }
+    }
+
+    /// Construct a new SourceClass containing every iterator being merged, in order.
+    pub fn any() -> SourceClass {
+        SourceClass { indices: None }
+    }
+
+    /// Returns an immutable reference to the indices of the iterators in this class, or None if
+    /// this class contains every iterator.
+    pub fn get_indices(&self) -> Option<&[isize]> {
+        self.indices.as_deref()
+    }
+}
+
+fn parse_unsigned_index(input: &str) -> IResult<&str, isize, Failure> {
+    parse_number(input)
+}
+
+fn parse_signed_index(input: &str) -> IResult<&str, isize, Failure> {
+    parse_signed_number(input)
+}
+
+/// The most indices that a class can have, so that a range like `[0-9999999999]` fails to parse
+/// instead of exhausting memory.
+pub(crate) const MAX_CLASS_SIZE: usize = 1 << 16;
+
+/// Fails without backtracking, like a number that's too large, if a class that was parsed from
+/// `input` up to `rest` has too many indices.
+fn check_class_size<'a>(
+    input: &'a str,
+    rest: &'a str,
+    size: usize,
+) -> Result<(), nom::Err<Failure>> {
+    if size > MAX_CLASS_SIZE {
+        return Err(Failure::overflow(input, input.len() - rest.len()));
+    }
+    Ok(())
+}
+
+fn parse_index_range(input: &str) -> IResult<&str, RangeInclusive<isize>, Failure> {
+    let (rest, (start, end)) = verify(
+        separated_pair(parse_unsigned_index, char('-'), parse_unsigned_index),
+        |(start, end): &(isize, isize)| start <= end,
+    )(input)?;
+    check_class_size(input, rest, (end - start) as usize + 1)?;
+    Ok((rest, start..=end))
+}
+
+/// Parses an index or a range of indices in a class, marked with the indices it's written as.
+fn parse_class_member(input: &str) -> IResult<&str, (Vec<isize>, Marked), Failure> {
+    let (rest, indices) = alt((
+        parse_index_range,
+        map(parse_signed_index, |index| index..=index),
+    ))(input)?;
+    let marked = Marked::new(Mark::Indices(indices.clone()), input, rest);
+    Ok((rest, (indices.collect(), marked)))
+}
+
+fn parse_any_class(input: &str) -> IResult<&str, SourceClass, Failure> {
+    let (input, _) = char('.')(input)?;
+    Ok((input, SourceClass::any()))
+}
+
+fn parse_listed_class(input: &str) -> IResult<&str, (SourceClass, Vec<Marked>), Failure> {
+    let (rest, (first, mut members)) = preceded(
+        char('['),
+        pair(
+            parse_class_member,
+            repeated_then(preceded(char(','), parse_class_member), char(']')),
+        ),
+    )(input)?;
+    members.insert(0, first);
+    let size = members
+        .iter()
+        .map(|(indices, _)| indices.len())
+        .sum::<usize>();
+    check_class_size(input, rest, size)?;
+    let (members, marks): (Vec<Vec<isize>>, Vec<Marked>) = members.into_iter().unzip();
+    let indices = members.into_iter().flatten().collect();
+    Ok((rest, (SourceClass::new(indices), marks)))
+}
+
+/// Prints the class as `.` or a list like `[0,2,4]`. A class of one iterator is printed as a
+/// range, like `[3-3]`, since `[3]` is a Single.
+impl fmt::Display for SourceClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get_indices() {
+            None => write!(f, "."),
+            Some([index]) if *index >= 0 => write!(f, "[{0}-{0}]", index),
+            Some(indices) => {
+                write!(f, "[")?;
+                for (position, index) in indices.iter().enumerate() {
+                    if position > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", index)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl ParserCombinator for SourceClass {
+    fn parse_marked(input: &str) -> IResult<&str, (SourceClass, Vec<Marked>), Failure> {
+        alt((unmarked(parse_any_class), parse_listed_class))(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_parses_lists_and_ranges() {
+        assert_eq!(
+            SourceClass::parse("[0,2,4]"),
+            Ok(("", SourceClass::new(vec![0, 2, 4])))
+        );
+        assert_eq!(
+            SourceClass::parse("[1-3]*"),
+            Ok(("*", SourceClass::new(vec![1, 2, 3])))
+        );
+        assert_eq!(
+            SourceClass::parse("[-1,0-2,12]"),
+            Ok(("", SourceClass::new(vec![-1, 0, 1, 2, 12])))
+        );
+    }
+
+    #[test]
+    fn dot_parses_any_class() {
+        assert_eq!(SourceClass::parse(".*"), Ok(("*", SourceClass::any())));
+    }
+
+    #[test]
+    fn bad_class_fails() {
+        SourceClass::parse("[]").unwrap_err();
+
+        SourceClass::parse("[3-1]").unwrap_err();
+
+        SourceClass::parse("[0,]").unwrap_err();
+
+        SourceClass::parse("[,0]").unwrap_err();
+
+        SourceClass::parse("[0--1]").unwrap_err();
+    }
+
+    #[test]
+    fn source_class_prints_as_written() {
+        assert_eq!(SourceClass::any().to_string(), ".");
+        assert_eq!(SourceClass::new(vec![0, 2, -1]).to_string(), "[0,2,-1]");
+        assert_eq!(SourceClass::new(vec![3]).to_string(), "[3-3]");
+        assert_eq!(
+            SourceClass::parse(&SourceClass::new(vec![3]).to_string()),
+            Ok(("", SourceClass::new(vec![3])))
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use nom::{
+    character::complete::{char, digit1},
+    combinator::{opt, recognize},
+    error::{ErrorKind, ParseError},
+    sequence::pair,
+    IResult,
+};
+use std::{
+    ops::{Range, RangeInclusive},
+    str::FromStr,
+};
+
+/// Trait for implementing parser-combinator-style parse functions
+pub trait ParserCombinator {
+    /// Defines how an object is parsed from a string.
+    ///
+    /// Returns a nom::IResult containing the Self that was parsed, with the marks within it in
+    /// the order they're written, and the remainder of the input string that wasn't parsed.
+    fn parse_marked(input: &str) -> IResult<&str, (Self, Vec<Marked>), Failure>
+    where
+        Self: std::marker::Sized;
+
+    /// Parses an object like [`parse_marked`](#tymethod.parse_marked), without its marks.
+    fn parse(input: &str) -> IResult<&str, Self, Failure>
+    where
+        Self: std::marker::Sized,
+    {
+        let (input, (parsed, _)) = Self::parse_marked(input)?;
+        Ok((input, parsed))
+    }
+}
+
+/// Something in an IMEx that's checked once it's parsed, so errors about it need its span.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub(crate) enum Mark {
+    /// The indices of iterators, written as one index like `3`, or as a range in a class like
+    /// `0-5`.
+    Indices(RangeInclusive<isize>),
+    /// The start of a named group, like `(?<name>`.
+    Definition(String),
+    /// A call to a named group, like `(&name)`.
+    Call(String),
+}
+
+/// A [`Mark`] with the span it was parsed from. Like a [`Failure`], the span is held as the
+/// amount of input that was left before and after it, since a parser doesn't know where its input
+/// starts.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Marked {
+    pub(crate) mark: Mark,
+    before: usize,
+    after: usize,
+}
+
+impl Marked {
+    /// Marks `mark` as parsed from `input`, leaving `rest`.
+    pub(crate) fn new(mark: Mark, input: &str, rest: &str) -> Marked {
+        Marked {
+            mark,
+            before: input.len(),
+            after: rest.len(),
+        }
+    }
+
+    /// Returns the span of the mark in `imex_str`, the whole input that was parsed.
+    pub(crate) fn span(&self, imex_str: &str) -> Range<usize> {
+        imex_str.len() - self.before..imex_str.len() - self.after
+    }
+}
+
+/// Something a parser expected to find, but didn't.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub(crate) enum Expected {
+    Char(char),
+    Digit,
+    End,
+}
+
+/// The error of the parser combinators. Holds the amount of input that was left where parsing
+/// failed, what was expected there, and the length of the number there if it was too large.
+///
+/// The parser combinators backtrack, so when alternatives fail, the failure that got furthest is
+/// kept, along with everything that was expected there, to explain why a whole IMEx failed to
+/// parse.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Failure {
+    pub(crate) remaining: usize,
+    pub(crate) expected: Vec<Expected>,
+    pub(crate) overflow: Option<usize>,
+}
+
+impl Failure {
+    fn new(input: &str, expected: Option<Expected>) -> Failure {
+        Failure {
+            remaining: input.len(),
+            expected: expected.into_iter().collect(),
+            overflow: None,
+        }
+    }
+
+    /// A failure that can't be backtracked from, for a number of `length` bytes at the start of
+    /// `input` that's too large.
+    pub(crate) fn overflow(input: &str, length: usize) -> nom::Err<Failure> {
+        nom::Err::Failure(Failure {
+            overflow: Some(length),
+            ..Failure::new(input, None)
+        })
+    }
+
+    /// Keeps whichever of `self` and `other` got further, merging what they expected if they
+    /// failed at the same place.
+    pub(crate) fn furthest(mut self, other: Failure) -> Failure {
+        if other.remaining < self.remaining {
+            return other;
+        }
+        if other.remaining == self.remaining {
+            for expected in other.expected {
+                if !self.expected.contains(&expected) {
+                    self.expected.push(expected);
+                }
+            }
+        }
+        self
+    }
+}
+
+impl ParseError<&str> for Failure {
+    fn from_error_kind(input: &str, kind: ErrorKind) -> Failure {
+        let expected = match kind {
+            ErrorKind::Digit | ErrorKind::OneOf => Some(Expected::Digit),
+            // Parsers of a single character fail with Eof at the end of the input, while
+            // all_consuming fails with it when there's input left.
+            ErrorKind::Eof if !input.is_empty() => Some(Expected::End),
+            _ => None,
+        };
+        Failure::new(input, expected)
+    }
+
+    fn from_char(input: &str, c: char) -> Failure {
+        Failure::new(input, Some(Expected::Char(c)))
+    }
+
+    fn or(self, other: Failure) -> Failure {
+        self.furthest(other)
+    }
+
+    fn append(_: &str, _: ErrorKind, other: Failure) -> Failure {
+        other
+    }
+}
+
+/// Runs `parser`, which parses something without marks, giving it no marks.
+pub(crate) fn unmarked<'a, O, P>(
+    parser: P,
+) -> impl Fn(&'a str) -> IResult<&'a str, (O, Vec<Marked>), Failure>
+where
+    P: Fn(&'a str) -> IResult<&'a str, O, Failure>,
+{
+    move |input| {
+        let (input, parsed) = parser(input)?;
+        Ok((input, (parsed, vec![])))
+    }
+}
+
+/// Splits marked items into the items and their marks, in order.
+pub(crate) fn split_marks<T>(items: Vec<(T, Vec<Marked>)>) -> (Vec<T>, Vec<Marked>) {
+    let (items, marks): (Vec<T>, Vec<Vec<Marked>>) = items.into_iter().unzip();
+    (items, marks.into_iter().flatten().collect())
+}
+
+/// Parses `item` as many times as it can, like nom's `many0`. The repetition only ends where an
+/// item fails without getting past its start, though. An item that fails further along fails the
+/// whole repetition without backtracking, since nothing else could be parsed there, and its
+/// failure is what went wrong.
+pub(crate) fn repeated<'a, O, P>(item: P) -> impl Fn(&'a str) -> IResult<&'a str, Vec<O>, Failure>
+where
+    P: Fn(&'a str) -> IResult<&'a str, O, Failure>,
+{
+    move |input| {
+        let (input, (items, _)) = repeat(&item, input)?;
+        Ok((input, items))
+    }
+}
+
+/// Parses `item` as many times as it can, like [`repeated`], then `end`. If `end` fails where the
+/// repetition ended, another item could have been given there instead, so the failures are merged.
+pub(crate) fn repeated_then<'a, O, E, P, Q>(
+    item: P,
+    end: Q,
+) -> impl Fn(&'a str) -> IResult<&'a str, Vec<O>, Failure>
+where
+    P: Fn(&'a str) -> IResult<&'a str, O, Failure>,
+    Q: Fn(&'a str) -> IResult<&'a str, E, Failure>,
+{
+    move |input| {
+        let (input, (items, stop)) = repeat(&item, input)?;
+        match end(input) {
+            Ok((input, _)) => Ok((input, items)),
+            Err(nom::Err::Error(failure)) => Err(nom::Err::Error(match stop {
+                Some(stop) => stop.furthest(failure),
+                None => failure,
+            })),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// Parses the items of a repetition, returning them along with the failure that ended it.
+fn repeat<'a, O, P>(
+    item: &P,
+    mut input: &'a str,
+) -> IResult<&'a str, (Vec<O>, Option<Failure>), Failure>
+where
+    P: Fn(&'a str) -> IResult<&'a str, O, Failure>,
+{
+    let mut items = vec![];
+    loop {
+        match item(input) {
+            Ok((rest, parsed)) if rest.len() < input.len() => {
+                items.push(parsed);
+                input = rest;
+            }
+            Ok(_) => return Ok((input, (items, None))),
+            Err(nom::Err::Error(failure)) if failure.remaining == input.len() => {
+                return Ok((input, (items, Some(failure))))
+            }
+            Err(nom::Err::Error(failure)) => return Err(nom::Err::Failure(failure)),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Parses a number, failing without backtracking if it's too large for `N`.
+pub(crate) fn parse_number<N: FromStr>(input: &str) -> IResult<&str, N, Failure> {
+    let (rest, digits) = digit1(input)?;
+    parsed(input, rest, digits)
+}
+
+/// Parses a number that may be negative, failing without backtracking if it's too large for `N`.
+pub(crate) fn parse_signed_number<N: FromStr>(input: &str) -> IResult<&str, N, Failure> {
+    let (rest, digits) = recognize(pair(opt(char('-')), digit1))(input)?;
+    parsed(input, rest, digits)
+}
+
+fn parsed<'a, N: FromStr>(
+    input: &'a str,
+    rest: &'a str,
+    digits: &str,
+) -> IResult<&'a str, N, Failure> {
+    match digits.parse::<N>() {
+        Ok(number) => Ok((rest, number)),
+        Err(_) => Err(Failure::overflow(input, digits.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::{branch::alt, character::complete::one_of, sequence::preceded};
+
+    #[test]
+    fn alternatives_keep_furthest_failure() {
+        let failure = alt((
+            preceded(char('x'), char('b')),
+            char('a'),
+            preceded(char('x'), char('c')),
+            one_of("0123456789"),
+        ))("xyz");
+
+        assert_eq!(
+            failure,
+            Err(nom::Err::Error(Failure {
+                remaining: 2,
+                expected: vec![Expected::Char('b'), Expected::Char('c')],
+                overflow: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn repetition_ends_where_item_fails_to_start() {
+        let pair = |input| preceded(char('a'), char('b'))(input);
+
+        assert_eq!(repeated(pair)("ababc"), Ok(("c", vec!['b', 'b'])));
+        assert_eq!(
+            repeated(pair)("abac"),
+            Err(nom::Err::Failure(Failure {
+                remaining: 1,
+                expected: vec![Expected::Char('b')],
+                overflow: None,
+            }))
+        );
+        assert_eq!(
+            repeated_then(pair, char(')'))("abc"),
+            Err(nom::Err::Error(Failure {
+                remaining: 1,
+                expected: vec![Expected::Char('a'), Expected::Char(')')],
+                overflow: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn too_large_number_fails() {
+        assert_eq!(parse_number::<u8>("255}"), Ok(("}", 255)));
+        assert_eq!(parse_signed_number::<i8>("-12]"), Ok(("]", -12)));
+        assert_eq!(
+            parse_number::<u8>("256}"),
+            Err(nom::Err::Failure(Failure {
+                remaining: 4,
+                expected: vec![],
+                overflow: Some(3)
+            }))
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{
+    parse_number, repeated_then, split_marks, Failure, IMEx, Marked, ParserCombinator, Resolved,
+};
+use nom::{
+    character::complete::char,
+    combinator::verify,
+    sequence::{pair, preceded},
+    IResult,
+};
+use std::{collections::HashMap, fmt, io::Result};
+
+/// Represents a weighted random choice in a parsed [`IMEx`](./struct.IMEx.html), written like
+/// `(0%70|1%30)`. Each alternative is followed by its weight, and iterating a WeightedChoice
+/// picks one alternative at random, so `(0%70|1%30)*` takes about 70% of its items from the
+/// first iterator.
+///
+/// As with an alternation, if the picked alternative finishes without taking any items, another
+/// is picked from the rest. The random choices are made by the random number generator of the
+/// iterators being merged, so they can be made reproducible with
+/// [`IMExIter::seeded`](https://docs.rs/imex/*/imex/struct.IMExIter.html#method.seeded).
+#[derive(PartialEq, Debug, Clone)]
+pub struct WeightedChoice {
+    alternatives: Vec<(IMEx, usize)>,
+}
+
+impl WeightedChoice {
+    /// Construct a new WeightedChoice from its alternatives and their weights.
+    pub fn new(alternatives: Vec<(IMEx, usize)>) -> WeightedChoice {
+        WeightedChoice { alternatives }
+    }
+
+    /// Returns an immutable reference to the alternatives and their weights.
+    pub fn get_alternatives(&self) -> &[(IMEx, usize)] {
+        &self.alternatives
+    }
+
+    /// Applies `f` to the IMEx of each alternative, keeping their weights.
+    fn map_imexes<F, E>(self, mut f: F) -> std::result::Result<WeightedChoice, E>
+    where
+        F: FnMut(IMEx) -> std::result::Result<IMEx, E>,
+    {
+        Ok(WeightedChoice::new(
+            self.alternatives
+                .into_iter()
+                .map(|(imex, weight)| Ok((f(imex)?, weight)))
+                .collect::<std::result::Result<Vec<(IMEx, usize)>, E>>()?,
+        ))
+    }
+
+    /// Resolves the names in each alternative into indices of `names`.
+    pub(crate) fn resolve_names<S: AsRef<str>>(self, names: &[S]) -> Result<WeightedChoice> {
+        self.map_imexes(|imex| imex.resolve_names(names))
+    }
+
+    /// Binds the parameters in each alternative to their counts in `params`.
+    pub(crate) fn bind_params(self, params: &HashMap<String, usize>) -> Result<WeightedChoice> {
+        self.map_imexes(|imex| imex.bind_params(params))
+    }
+
+    /// Resolves the calls to named groups in each alternative.
+    pub(crate) fn resolve_calls(
+        self,
+        definitions: &HashMap<String, IMEx>,
+        callers: &mut Vec<String>,
+    ) -> Resolved<WeightedChoice> {
+        self.map_imexes(|imex| imex.resolve_calls(definitions, callers))
+    }
+}
+
+impl fmt::Display for WeightedChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        for (position, (imex, weight)) in self.alternatives.iter().enumerate() {
+            if position > 0 {
+                write!(f, "|")?;
+            }
+            write!(f, "{}%{}", imex, weight)?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn parse_weight(input: &str) -> IResult<&str, usize, Failure> {
+    verify(parse_number, |weight: &usize| *weight > 0)(input)
+}
+
+/// An alternative of a WeightedChoice and its weight, with the marks within it.
+pub(crate) type MarkedAlternative = ((IMEx, usize), Vec<Marked>);
+
+/// The weight of the first alternative of a WeightedChoice, followed by its other alternatives.
+pub(crate) type WeightedTail = (usize, Vec<MarkedAlternative>);
+
+fn parse_weighted_alternative(input: &str) -> IResult<&str, MarkedAlternative, Failure> {
+    let (input, ((imex, marks), weight)) =
+        pair(IMEx::parse_marked, preceded(char('%'), parse_weight))(input)?;
+    Ok((input, ((imex, weight), marks)))
+}
+
+/// Parses what follows the IMEx of the first alternative of a WeightedChoice, up to the closing
+/// `)`. A group starts like a WeightedChoice, so the first IMEx is parsed once for both, and
+/// nested groups aren't parsed again for each thing they could be.
+pub(crate) fn parse_weighted_tail(input: &str) -> IResult<&str, WeightedTail, Failure> {
+    pair(
+        preceded(char('%'), parse_weight),
+        repeated_then(preceded(char('|'), parse_weighted_alternative), char(')')),
+    )(input)
+}
+
+impl WeightedChoice {
+    /// Constructs a WeightedChoice from the IMEx of its first alternative and the tail that
+    /// followed it, along with the marks within them.
+    pub(crate) fn from_tail(
+        (first, first_marks): (IMEx, Vec<Marked>),
+        (weight, mut alternatives): WeightedTail,
+    ) -> (WeightedChoice, Vec<Marked>) {
+        alternatives.insert(0, ((first, weight), first_marks));
+        let (alternatives, marks) = split_marks(alternatives);
+        (WeightedChoice::new(alternatives), marks)
+    }
+}
+
+impl ParserCombinator for WeightedChoice {
+    fn parse_marked(input: &str) -> IResult<&str, (WeightedChoice, Vec<Marked>), Failure> {
+        let (input, _) = char('(')(input)?;
+        let (input, first) = IMEx::parse_marked(input)?;
+        let (input, tail) = parse_weighted_tail(input)?;
+        Ok((input, WeightedChoice::from_tail(first, tail)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn weighted_choice_parses() -> Result<()> {
+        assert_eq!(
+            WeightedChoice::parse("(0%70|12%30)*"),
+            Ok((
+                "*",
+                WeightedChoice::new(vec![
+                    (IMEx::try_from("0")?, 70),
+                    (IMEx::try_from("12")?, 30)
+                ])
+            ))
+        );
+        assert_eq!(
+            WeightedChoice::parse("(0%1)"),
+            Ok(("", WeightedChoice::new(vec![(IMEx::try_from("0")?, 1)])))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_weighted_choice_fails() {
+        WeightedChoice::parse("(0%70|1)").unwrap_err();
+
+        WeightedChoice::parse("(0|1%30)").unwrap_err();
+
+        WeightedChoice::parse("(0%0|1%1)").unwrap_err();
+
+        WeightedChoice::parse("(0%)").unwrap_err();
+
+        WeightedChoice::parse("(0%-1)").unwrap_err();
+
+        WeightedChoice::parse("(0%1").unwrap_err();
+    }
+
+    #[test]
+    fn weighted_choice_prints_as_written() -> Result<()> {
+        let choice =
+            WeightedChoice::new(vec![(IMEx::try_from("0*")?, 70), (IMEx::try_from("")?, 30)]);
+
+        assert_eq!(choice.to_string(), "(0*%70|%30)");
+        Ok(())
+    }
+}
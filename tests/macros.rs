@@ -0,0 +1,31 @@
+#![cfg(feature = "macros")]
+
+use imex::{imex, IMExIter, Pattern};
+use std::io::Result;
+
+macro_rules! assert_parsed_like_new {
+    ($($imex:literal),*) => {
+        $(assert_eq!(imex!($imex), Pattern::new($imex)?, "{}", $imex);)*
+    };
+}
+
+#[test]
+fn macro_builds_parsed_pattern() -> Result<()> {
+    assert_parsed_like_new!(
+        "0(01){4}",
+        "[-1,2-4]*.+0?1{2,}2{,3}3{1,5}",
+        "(?<ab>a[bee])(&ab){$n}",
+        "(0%70|1%30)(0|1)<0[1]>~0$1(?0:1)(?!1:0)",
+        "(?x) 0 # the first item
+              1* # then the rest"
+    );
+    Ok(())
+}
+
+#[test]
+fn macro_pattern_merges() -> Result<()> {
+    let merged = IMExIter::new(vec!["123456".chars(), "abc".chars()], imex!("0(01){4}"))?
+        .collect::<String>();
+    assert_eq!(merged, "12a3b4c5");
+    Ok(())
+}